@@ -4,7 +4,6 @@ use sdl2::audio::{AudioCallback, AudioSpecDesired};
 use sdl2::AudioSubsystem;
 use std::time::Duration;
 use std::sync::mpsc;
-use std::i16;
 
 const RECORDING_LENGTH_SECONDS: usize = 3;
 
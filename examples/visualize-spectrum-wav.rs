@@ -1,6 +1,5 @@
 extern crate sdl2;
 extern crate chord_detection;
-extern crate goertzel;
 extern crate pitch_calc;
 extern crate hound;
 
@@ -18,9 +17,13 @@ use chord_detection::midi_notes;
 
 use sdl2::audio::{AudioCallback, AudioSpecDesired};
 use std::sync::mpsc;
-use std::i16::MAX as I16_MAX;
 use chord_detection::utils::make_mono;
+use chord_detection::chord::Chord;
 use chord_detection::chord_detection::ChordDetector;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+const MODEL_FILENAME: &str = "liebt-uns-chords.model";
 
 
 struct PseudoRecording {
@@ -50,8 +53,6 @@ fn read_wav(filename: &str) -> Vec<i16> {
 
 pub fn main() {
     let wav_data = read_wav("liebt-uns.wav");
-    let training_input = vec![read_wav("liebt-uns-a.wav"), read_wav("liebt-uns-e.wav")];
-    let training_labels = vec!["a", "e"];
 
     sdl2::ttf::get_linked_version();
     let sdl_context = sdl2::init().unwrap();
@@ -87,16 +88,24 @@ pub fn main() {
         sample_rate: capture_freq,
         start_note: midi_notes::A1 as usize,
         notes_count: 24,
+        ..Default::default()
     };
 
     let mut ggram = Gromagram::new(gromagram_init_props.clone());
 
+    let training_labels: Vec<Chord> = vec!["A".parse().unwrap(), "E".parse().unwrap()];
     let mut chord_detector = ChordDetector::new(Gromagram::new(gromagram_init_props), &training_labels);
-
-    for (input, label) in training_input.iter().zip(training_labels.iter()) {
-        chord_detector.train(input, label);
+    let loaded = File::open(MODEL_FILENAME).map_err(|error| error.into())
+        .and_then(|file| chord_detector.load(BufReader::new(file)));
+    if let Err(error) = loaded {
+        println!("Training, because the model can't be loaded: {}", error);
+        let training_input = [read_wav("liebt-uns-a.wav"), read_wav("liebt-uns-e.wav")];
+        for (input, label) in training_input.iter().zip(training_labels.iter()) {
+            chord_detector.train_transposed(input, label).unwrap();
+        }
+        chord_detector.finish_training().unwrap();
+        chord_detector.save(&mut BufWriter::new(File::create(MODEL_FILENAME).unwrap())).unwrap();
     }
-    chord_detector.finish_training();
 
     println!("AudioDriver: {:?}", capture_device.subsystem().current_audio_driver());
     capture_device.resume();
@@ -138,7 +147,7 @@ pub fn main() {
             canvas.set_draw_color(draw_color);
             let audio_chunk = &audio_frames[0];
             for (x, &audio_val) in audio_chunk.iter().enumerate() {
-                canvas.draw_point(Point::new(x as i32, middle_h + audio_val as i32 * middle_h / I16_MAX as i32)).unwrap();
+                canvas.draw_point(Point::new(x as i32, middle_h + audio_val as i32 * middle_h / i16::MAX as i32)).unwrap();
             }
 
             for frame in &audio_frames {
@@ -151,7 +160,7 @@ pub fn main() {
             for (i, a_mag) in ggram.gromagram.iter().enumerate() {
                 canvas.set_draw_color(Color::RGB(0, 0, 255));
                 let y = (i as u32) * bar_height;
-                canvas.draw_rect(Rect::new(0, y as i32, (a_mag / 5000.0) as u32, bar_height)).unwrap();
+                canvas.draw_rect(Rect::new(0, y as i32, (a_mag * 6.5) as u32, bar_height)).unwrap();
             }
 
             ggram.normalize();
@@ -164,7 +173,7 @@ pub fn main() {
             }
 
             let chord = chord_detector.detect(&ggram.gromagram);
-            eprintln!("chord = {:?}", chord);
+            eprintln!("chord = {}", chord.map_or("?".to_string(), |chord| chord.lead_sheet()));
 
 //            canvas.copy(&text_texture, None, Some(Rect::new(0, 0, t_width, t_height))).unwrap();
 
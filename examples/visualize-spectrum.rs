@@ -1,6 +1,5 @@
 extern crate sdl2;
 extern crate chord_detection;
-extern crate goertzel;
 extern crate pitch_calc;
 
 use std::path::Path;
@@ -19,18 +18,13 @@ use chord_detection::gromagram::{Gromagram, GromagramInitProps};
 use chord_detection::chromagram::{Chromagram, ChromagramInitProps};
 use chord_detection::midi_notes;
 use chord_detection::utils::make_mono;
-use chord_detection::chord_detection::ChordDetector;
 
 use sdl2::audio::{AudioCallback, AudioSpecDesired};
 use std::sync::mpsc;
-use std::i16::MAX as I16_MAX;
 
 use pitch_calc::{
-    Hz,
     Letter,
     LetterOctave,
-    ScaledPerc,
-    Step,
 };
 
 
@@ -80,19 +74,16 @@ pub fn main() {
         }
     }).unwrap();
 
-    let mut input_buffer = vec![0.0; sample_count];
     let mut mono_buffer = vec![0; sample_count];
 
-    let mut chromagram = Chromagram::new(ChromagramInitProps {
-        sample_rate: capture_freq as usize,
-        frame_size: sample_count,
-    });
+    let mut chromagram = Chromagram::new(ChromagramInitProps::new(sample_count, capture_freq as usize)).unwrap();
 
     let mut ggram = Gromagram::new(GromagramInitProps {
         window_size: 1024 * 2,
         sample_rate: capture_freq,
         start_note: midi_notes::A1 as usize,
         notes_count: 24,
+        ..Default::default()
     }
     );
 
@@ -151,12 +142,12 @@ pub fn main() {
             canvas.set_draw_color(draw_color);
             let audio_chunk = &audio_frames[0];
             for (x, &audio_val) in audio_chunk.iter().enumerate() {
-                canvas.draw_point(Point::new(x as i32, middle_h + audio_val as i32 * middle_h / I16_MAX as i32)).unwrap();
+                canvas.draw_point(Point::new(x as i32, middle_h + audio_val as i32 * middle_h / i16::MAX as i32)).unwrap();
             }
 
             canvas.set_draw_color(Color::RGB(255, 0, 0));
             let spectrum = calculate_spectrum(audio_chunk);
-            let max_spectrum_now: f32 = *spectrum.iter().max_by_key(|&&f| f as i16).unwrap();
+            let max_spectrum_now: f64 = *spectrum.iter().max_by_key(|&&f| f as i16).unwrap();
             if max_spectrum_now > max_spectrum {
                 max_spectrum = max_spectrum_now;
             }
@@ -174,15 +165,12 @@ pub fn main() {
             for (i, a_mag) in ggram.gromagram.iter().enumerate() {
                 canvas.set_draw_color(Color::RGB(0, 0, 255));
                 let y = (i as i32) * 20;
-                canvas.draw_rect(Rect::new(0, y, (a_mag / 5000.0) as u32, 10)).unwrap();
+                canvas.draw_rect(Rect::new(0, y, (a_mag * 6.5) as u32, 10)).unwrap();
             }
 
             for frame in &audio_frames {
-                for (i, chunk) in frame.chunks(channel_count).enumerate() {
-                    let mut s: i64 = chunk.iter().map(|&x| x as i64).sum();
-                    input_buffer[i] = s as f64 / channel_count as f64;
-                }
-                chromagram.process_audio_frame(&input_buffer);
+                make_mono(channel_count, frame, &mut mono_buffer[..]);
+                chromagram.process_audio_frame(&mono_buffer).unwrap();
             }
 
 //            if chromagram.is_ready() {
@@ -9,7 +9,6 @@ use sdl2::rect::Point;
 
 use sdl2::audio::{AudioCallback, AudioSpecDesired};
 use std::sync::mpsc;
-use std::i16::MAX as I16_MAX;
 
 struct Recording {
     new_frame_sender: mpsc::Sender<Vec<i16>>,
@@ -79,7 +78,7 @@ pub fn main() {
             canvas.set_draw_color(draw_color);
             let audio_chunk = &audio_frames[0];
             for (x, &audio_val) in audio_chunk.iter().enumerate() {
-                canvas.draw_point(Point::new(x as i32, middle_h + audio_val as i32 * middle_h / I16_MAX as i32)).unwrap();
+                canvas.draw_point(Point::new(x as i32, middle_h + audio_val as i32 * middle_h / i16::MAX as i32)).unwrap();
             }
             x = (x + 1) % 400;
         }
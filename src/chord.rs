@@ -0,0 +1,666 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use template_chord_detection::{ChordQuality, PITCH_CLASS_NAMES};
+
+const NUM_PITCH_CLASSES: usize = 12;
+
+// semitones of the degrees 1 to 7 of the major scale
+const MAJOR_SCALE: [usize; 7] = [0, 2, 4, 5, 7, 9, 11];
+
+// Harte shorthands, which aren't a quality of their own, with their extensions
+const HARTE_ALIASES: [(&str, ChordQuality, &[Degree]); 3] = [
+    ("9", ChordQuality::Dominant7, &[Degree::NINTH]),
+    ("maj9", ChordQuality::Major7, &[Degree::NINTH]),
+    ("min9", ChordQuality::Minor7, &[Degree::NINTH]),
+];
+
+// lead sheet suffixes, the longest matching suffix is used
+const LEAD_SHEET_SUFFIXES: [(&str, ChordQuality, &[Degree]); 48] = [
+    ("", ChordQuality::Major, &[]),
+    ("maj", ChordQuality::Major, &[]),
+    ("M", ChordQuality::Major, &[]),
+    ("m", ChordQuality::Minor, &[]),
+    ("min", ChordQuality::Minor, &[]),
+    ("-", ChordQuality::Minor, &[]),
+    ("dim", ChordQuality::Diminished, &[]),
+    ("o", ChordQuality::Diminished, &[]),
+    ("°", ChordQuality::Diminished, &[]),
+    ("aug", ChordQuality::Augmented, &[]),
+    ("+", ChordQuality::Augmented, &[]),
+    ("sus2", ChordQuality::Sus2, &[]),
+    ("sus4", ChordQuality::Sus4, &[]),
+    ("sus", ChordQuality::Sus4, &[]),
+    ("maj7", ChordQuality::Major7, &[]),
+    ("M7", ChordQuality::Major7, &[]),
+    ("Δ", ChordQuality::Major7, &[]),
+    ("Δ7", ChordQuality::Major7, &[]),
+    ("m7", ChordQuality::Minor7, &[]),
+    ("min7", ChordQuality::Minor7, &[]),
+    ("-7", ChordQuality::Minor7, &[]),
+    ("7", ChordQuality::Dominant7, &[]),
+    ("dim7", ChordQuality::Diminished7, &[]),
+    ("o7", ChordQuality::Diminished7, &[]),
+    ("°7", ChordQuality::Diminished7, &[]),
+    ("m7b5", ChordQuality::HalfDiminished7, &[]),
+    ("ø", ChordQuality::HalfDiminished7, &[]),
+    ("ø7", ChordQuality::HalfDiminished7, &[]),
+    ("mmaj7", ChordQuality::MinorMajor7, &[]),
+    ("mMaj7", ChordQuality::MinorMajor7, &[]),
+    ("m(maj7)", ChordQuality::MinorMajor7, &[]),
+    ("6", ChordQuality::Major6, &[]),
+    ("m6", ChordQuality::Minor6, &[]),
+    ("5", ChordQuality::Power, &[]),
+    ("9", ChordQuality::Dominant7, &[Degree::NINTH]),
+    ("maj9", ChordQuality::Major7, &[Degree::NINTH]),
+    ("m9", ChordQuality::Minor7, &[Degree::NINTH]),
+    ("add9", ChordQuality::Major, &[Degree::NINTH]),
+    ("madd9", ChordQuality::Minor, &[Degree::NINTH]),
+    ("6/9", ChordQuality::Major6, &[Degree::NINTH]),
+    ("m6/9", ChordQuality::Minor6, &[Degree::NINTH]),
+    ("7sus4", ChordQuality::Sus4, &[Degree::FLAT_SEVENTH]),
+    ("7sus", ChordQuality::Sus4, &[Degree::FLAT_SEVENTH]),
+    ("7sus2", ChordQuality::Sus2, &[Degree::FLAT_SEVENTH]),
+    ("11", ChordQuality::Dominant7, &[Degree::NINTH, Degree::ELEVENTH]),
+    ("m11", ChordQuality::Minor7, &[Degree::NINTH, Degree::ELEVENTH]),
+    // the 11th is usually left out, because it clashes with the major third
+    ("13", ChordQuality::Dominant7, &[Degree::NINTH, Degree::THIRTEENTH]),
+    ("m13", ChordQuality::Minor7, &[Degree::NINTH, Degree::ELEVENTH, Degree::THIRTEENTH]),
+];
+
+/// A scale degree relative to the root of a chord, e.g. b7 or #11.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Degree {
+    number: usize,
+    alteration: isize,
+}
+
+impl Degree {
+    pub const FLAT_SEVENTH: Degree = Degree { number: 7, alteration: -1 };
+    pub const NINTH: Degree = Degree { number: 9, alteration: 0 };
+    pub const ELEVENTH: Degree = Degree { number: 11, alteration: 0 };
+    pub const THIRTEENTH: Degree = Degree { number: 13, alteration: 0 };
+
+    /// The degree of the major scale with the number, altered by the semitones.
+    ///
+    /// # Panics
+    /// If the number isn't between 1 and 13, parse untrusted input with `FromStr` instead.
+    pub fn new(number: usize, alteration: isize) -> Self {
+        assert!((1..=13).contains(&number), "degree must be between 1 and 13");
+        Self { number, alteration }
+    }
+
+    /// 1 to 13
+    pub fn number(self) -> usize {
+        self.number
+    }
+
+    /// Semitones added to the degree of the major scale, -1 = flat, 1 = sharp
+    pub fn alteration(self) -> isize {
+        self.alteration
+    }
+
+    /// Semitones above the root, may be larger than an octave.
+    pub fn semitones(self) -> usize {
+        let octaves = (self.number - 1) / 7;
+        let semitones = (MAJOR_SCALE[(self.number - 1) % 7] + 12 * octaves) as isize + self.alteration;
+        semitones.rem_euclid(12 * (octaves as isize + 1)) as usize
+    }
+
+    /// The usual spelling of an interval within the octave.
+    fn from_semitones(semitones: usize) -> Self {
+        let (number, alteration) = [(1, 0), (2, -1), (2, 0), (3, -1), (3, 0), (4, 0), (5, -1), (5, 0), (5, 1), (6, 0), (7, -1), (7, 0)]
+            [semitones % NUM_PITCH_CLASSES];
+        Self::new(number, alteration)
+    }
+}
+
+impl fmt::Display for Degree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let accidental = if self.alteration < 0 { "b" } else { "#" };
+        write!(f, "{}{}", accidental.repeat(self.alteration.unsigned_abs()), self.number)
+    }
+}
+
+impl FromStr for Degree {
+    type Err = ParseChordError;
+
+    fn from_str(s: &str) -> Result<Self, ParseChordError> {
+        let number_start = s.find(|c: char| c.is_ascii_digit()).unwrap_or(s.len());
+        let (accidentals, number) = s.split_at(number_start);
+        let alteration = accidentals.chars().try_fold(0, |alteration, c| match c {
+            'b' | '♭' => Ok(alteration - 1),
+            '#' | '♯' => Ok(alteration + 1),
+            _ => Err(ParseChordError::new(s, "invalid accidental in degree")),
+        })?;
+        match number.parse() {
+            Ok(number) if (1..=13).contains(&number) => Ok(Degree::new(number, alteration)),
+            _ => Err(ParseChordError::new(s, "degree must be a number between 1 and 13")),
+        }
+    }
+}
+
+/// A chord label, either no chord (e.g. silence or noise) or a chord with root, quality, extensions and bass.
+///
+/// `Display` writes the syntax of Harte et al. (2005), which is used by MIREX and the Isophonics annotations,
+/// e.g. "A:min7/b3" or "N". `FromStr` also understands common lead sheet symbols like "Cmaj7/E" or "F#m7b5":
+/// a root, one of the usual suffixes (e.g. "m7", "maj9", "7sus4", "6/9"), optionally suspended by a following
+/// "sus2", "sus4" or "sus" ("C9sus4"), then altered degrees ("b9", "#11"), "add" and "no" degrees,
+/// each alone or in parentheses ("C7(b9,#11)", "C(add9)", "C(no3)"), and a slash bass note.
+/// Other notations, e.g. "alt", "omit", "H" or Roman numerals, are rejected.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Chord {
+    NoChord,
+    Symbol(ChordSymbol),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ChordSymbol {
+    /// Pitch class of the root, 0 = C
+    pub root: usize,
+    pub quality: ChordQuality,
+    /// Degrees added to the quality, sorted
+    pub extensions: Vec<Degree>,
+    /// Degrees of the quality, which are left out, sorted
+    pub omissions: Vec<Degree>,
+    /// Pitch class of the bass note, equal to the root in root position
+    pub bass: usize,
+}
+
+impl Chord {
+    /// A chord in root position without extensions.
+    pub fn new(root: usize, quality: ChordQuality) -> Self {
+        Chord::Symbol(ChordSymbol {
+            root: root % NUM_PITCH_CLASSES,
+            quality,
+            extensions: Vec::new(),
+            omissions: Vec::new(),
+            bass: root % NUM_PITCH_CLASSES,
+        })
+    }
+
+    /// Sets the pitch class of the bass note. No chord stays unchanged.
+    pub fn with_bass(mut self, bass: usize) -> Self {
+        if let Chord::Symbol(ref mut symbol) = self {
+            symbol.bass = bass % NUM_PITCH_CLASSES;
+        }
+        self
+    }
+
+    pub fn with_extension(mut self, degree: Degree) -> Self {
+        if let Chord::Symbol(ref mut symbol) = self {
+            if !symbol.quality.intervals().contains(&degree.semitones()) && !symbol.extensions.contains(&degree) {
+                symbol.extensions.push(degree);
+                symbol.extensions.sort();
+            }
+        }
+        self
+    }
+
+    pub fn is_no_chord(&self) -> bool {
+        *self == Chord::NoChord
+    }
+
+    pub fn symbol(&self) -> Option<&ChordSymbol> {
+        match *self {
+            Chord::NoChord => None,
+            Chord::Symbol(ref symbol) => Some(symbol),
+        }
+    }
+
+    pub fn root(&self) -> Option<usize> {
+        self.symbol().map(|symbol| symbol.root)
+    }
+
+    pub fn quality(&self) -> Option<ChordQuality> {
+        self.symbol().map(|symbol| symbol.quality)
+    }
+
+    pub fn bass(&self) -> Option<usize> {
+        self.symbol().map(|symbol| symbol.bass)
+    }
+
+    /// The pitch classes of all chord tones including the bass, sorted. Empty for no chord.
+    pub fn pitch_classes(&self) -> Vec<usize> {
+        let symbol = match self.symbol() {
+            Some(symbol) => symbol,
+            None => return Vec::new(),
+        };
+        let omitted: Vec<usize> = symbol.omissions.iter().map(|degree| degree.semitones()).collect();
+        let mut pitch_classes: Vec<usize> = symbol.quality.intervals().iter().cloned()
+            .filter(|interval| !omitted.contains(interval))
+            .chain(symbol.extensions.iter().map(|degree| degree.semitones()))
+            .map(|interval| (symbol.root + interval) % NUM_PITCH_CLASSES)
+            .chain(Some(symbol.bass))
+            .collect();
+        pitch_classes.sort();
+        pitch_classes.dedup();
+        pitch_classes
+    }
+
+    /// Lead sheet symbol like "Am7/G", "N.C." for no chord.
+    pub fn lead_sheet(&self) -> String {
+        let symbol = match self.symbol() {
+            Some(symbol) => symbol,
+            None => return "N.C.".to_string(),
+        };
+        let mut text = format!("{}{}", PITCH_CLASS_NAMES[symbol.root], symbol.quality.suffix());
+        let is_triad = symbol.quality.intervals().len() == 3;
+        if is_triad && symbol.extensions == [Degree::NINTH] && symbol.omissions.is_empty() {
+            text += "add9";
+        } else if !symbol.extensions.is_empty() || !symbol.omissions.is_empty() {
+            let degrees: Vec<String> = symbol.extensions.iter().map(|degree| degree.to_string())
+                .chain(symbol.omissions.iter().map(|degree| format!("no{}", degree)))
+                .collect();
+            text += &format!("({})", degrees.join(","));
+        }
+        if symbol.bass != symbol.root {
+            text += &format!("/{}", PITCH_CLASS_NAMES[symbol.bass]);
+        }
+        text
+    }
+}
+
+/// Labels, which can be moved to another key, e.g. for training chords in all keys from one recording.
+pub trait Transpose {
+    /// The label moved up by the semitones.
+    fn transposed(&self, semitones: usize) -> Self;
+}
+
+impl Transpose for Chord {
+    /// Moves root and bass, no chord stays unchanged.
+    fn transposed(&self, semitones: usize) -> Self {
+        match *self {
+            Chord::NoChord => Chord::NoChord,
+            Chord::Symbol(ref symbol) => Chord::Symbol(ChordSymbol {
+                root: (symbol.root + semitones) % NUM_PITCH_CLASSES,
+                bass: (symbol.bass + semitones) % NUM_PITCH_CLASSES,
+                ..symbol.clone()
+            }),
+        }
+    }
+}
+
+impl ChordSymbol {
+    /// Spelling of the bass as degree, preferring the degrees of the chord tones.
+    fn bass_degree(&self) -> Degree {
+        let interval = (self.bass + NUM_PITCH_CLASSES - self.root) % NUM_PITCH_CLASSES;
+        let chord_degrees = [(3, -1), (3, 0), (5, -1), (5, 1), (7, -2), (7, -1), (7, 0), (6, 0), (2, 0), (4, 0)];
+        chord_degrees.iter()
+            .map(|&(number, alteration)| Degree::new(number, alteration))
+            .filter(|degree| self.quality.intervals().contains(&degree.semitones()) || self.extensions.contains(degree))
+            .find(|degree| degree.semitones() % NUM_PITCH_CLASSES == interval)
+            .unwrap_or_else(|| Degree::from_semitones(interval))
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match *self {
+            Chord::NoChord => return write!(f, "N"),
+            Chord::Symbol(ref symbol) => symbol,
+        };
+        write!(f, "{}:{}", PITCH_CLASS_NAMES[symbol.root], symbol.quality.harte_shorthand())?;
+        if !symbol.extensions.is_empty() || !symbol.omissions.is_empty() {
+            let degrees: Vec<String> = symbol.extensions.iter().map(|degree| degree.to_string())
+                .chain(symbol.omissions.iter().map(|degree| format!("*{}", degree)))
+                .collect();
+            write!(f, "({})", degrees.join(","))?;
+        }
+        if symbol.bass != symbol.root {
+            write!(f, "/{}", symbol.bass_degree())?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Chord {
+    type Err = ParseChordError;
+
+    /// Parses Harte syntax ("C:maj7/3", "Db:(1,5)", "N") and lead sheet symbols ("Cmaj7/E", "F#m7b5", "N.C.").
+    fn from_str(s: &str) -> Result<Self, ParseChordError> {
+        let s = s.trim();
+        if ["N", "N.C.", "NC"].contains(&s) {
+            return Ok(Chord::NoChord);
+        }
+
+        let (root, rest) = parse_pitch_class(s).ok_or_else(|| ParseChordError::new(s, "chord must start with a root note"))?;
+        let (quality_part, bass_part) = match rest.rfind('/') {
+            // 6/9 is a chord, not a slash chord
+            Some(i) if !rest.ends_with("6/9") => (&rest[..i], Some(&rest[i + 1..])),
+            _ => (rest, None),
+        };
+
+        // a degree list without shorthand like "(1,5)" is only Harte syntax after the colon, "C(9)" is Cadd9
+        let (quality, mut extensions, mut omissions) = if let Some(harte) = quality_part.strip_prefix(':') {
+            parse_harte_quality(s, harte)?
+        } else {
+            parse_lead_sheet_quality(s, quality_part)?
+        };
+
+        let intervals = quality.intervals();
+        extensions.retain(|degree| !intervals.contains(&degree.semitones()));
+        extensions.sort();
+        extensions.dedup();
+        omissions.sort();
+        omissions.dedup();
+        let mut chord = Chord::Symbol(ChordSymbol { root, quality, extensions, omissions, bass: root });
+
+        if let Some(bass) = bass_part {
+            let bass = match parse_pitch_class(bass) {
+                Some((pitch_class, "")) => pitch_class,
+                Some(_) => return Err(ParseChordError::new(s, "invalid bass note")),
+                None => (root + bass.parse::<Degree>()?.semitones()) % NUM_PITCH_CLASSES,
+            };
+            chord = chord.with_bass(bass);
+        }
+        Ok(chord)
+    }
+}
+
+/// Note name with any number of accidentals at the start of the text, and the rest of the text.
+fn parse_pitch_class(s: &str) -> Option<(usize, &str)> {
+    let mut chars = s.chars();
+    let natural: isize = match chars.next()? {
+        'C' => 0, 'D' => 2, 'E' => 4, 'F' => 5, 'G' => 7, 'A' => 9, 'B' => 11,
+        _ => return None,
+    };
+    let mut rest = chars.as_str();
+    let mut alteration = 0;
+    loop {
+        if let Some(r) = rest.strip_prefix('b').or_else(|| rest.strip_prefix('♭')) {
+            alteration -= 1;
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix('#').or_else(|| rest.strip_prefix('♯')) {
+            alteration += 1;
+            rest = r;
+        } else {
+            break;
+        }
+    }
+    Some(((natural + alteration).rem_euclid(NUM_PITCH_CLASSES as isize) as usize, rest))
+}
+
+type ParsedQuality = (ChordQuality, Vec<Degree>, Vec<Degree>);
+
+/// Shorthand with an optional degree list like "min7(9,*5)", or only a degree list like "(1,5)".
+fn parse_harte_quality(chord: &str, s: &str) -> Result<ParsedQuality, ParseChordError> {
+    let (shorthand, list) = match s.find('(') {
+        Some(i) if s.ends_with(')') => (&s[..i], Some(&s[i + 1..s.len() - 1])),
+        Some(_) => return Err(ParseChordError::new(chord, "unclosed degree list")),
+        None => (s, None),
+    };
+    let (mut extensions, mut omissions) = (Vec::new(), Vec::new());
+    for item in list.into_iter().flat_map(|list| list.split(',')).map(str::trim) {
+        match item.strip_prefix('*') {
+            Some(omitted) => omissions.push(omitted.parse()?),
+            None => extensions.push(item.parse()?),
+        }
+    }
+
+    if shorthand.is_empty() && list.is_some() {
+        return Ok(quality_of_degrees(&extensions, omissions));
+    }
+    if shorthand.is_empty() {
+        return Err(ParseChordError::new(chord, "missing Harte shorthand"));
+    }
+    if let Some(quality) = ChordQuality::ALL.iter().find(|quality| quality.harte_shorthand() == shorthand) {
+        return Ok((*quality, extensions, omissions));
+    }
+    match HARTE_ALIASES.iter().find(|alias| alias.0 == shorthand) {
+        Some(&(_, quality, alias_extensions)) => {
+            extensions.extend_from_slice(alias_extensions);
+            Ok((quality, extensions, omissions))
+        }
+        None => Err(ParseChordError::new(chord, "unknown Harte shorthand")),
+    }
+}
+
+/// The quality of a chord given by a degree list only, e.g. (1,b3,5) is minor.
+/// Lists without a matching quality are written as major chord with extensions and omissions.
+fn quality_of_degrees(degrees: &[Degree], omissions: Vec<Degree>) -> ParsedQuality {
+    let omitted: Vec<usize> = omissions.iter().map(|degree| degree.semitones()).collect();
+    let mut intervals: Vec<usize> = Some(0).into_iter()
+        .chain(degrees.iter().map(|degree| degree.semitones()))
+        .filter(|interval| !omitted.contains(interval))
+        .collect();
+    intervals.sort();
+    intervals.dedup();
+    if let Some(quality) = ChordQuality::ALL.iter().find(|quality| quality.intervals() == &intervals[..]) {
+        return (*quality, Vec::new(), Vec::new());
+    }
+
+    let major = ChordQuality::Major.intervals();
+    let extensions = degrees.iter().cloned().filter(|degree| !major.contains(&degree.semitones())).collect();
+    let omissions = major.iter()
+        .filter(|interval| !intervals.contains(interval))
+        .map(|&interval| Degree::from_semitones(interval))
+        .collect();
+    (ChordQuality::Major, extensions, omissions)
+}
+
+/// Suffix like "m7b5", followed by a suspension like "sus4" and extensions like "b9", "add11", "(9,#11)" or "(no3)".
+fn parse_lead_sheet_quality(chord: &str, s: &str) -> Result<ParsedQuality, ParseChordError> {
+    let &(suffix, mut quality, suffix_extensions) = LEAD_SHEET_SUFFIXES.iter()
+        .filter(|entry| s.starts_with(entry.0))
+        .max_by_key(|entry| entry.0.len())
+        .expect("the empty suffix always matches");
+    let (mut extensions, mut omissions) = (suffix_extensions.to_vec(), Vec::new());
+
+    let mut rest = &s[suffix.len()..];
+    while !rest.is_empty() {
+        if let Some(&(sus, suspended)) = SUSPENSIONS.iter().find(|suspension| rest.starts_with(suspension.0)) {
+            extensions.extend(suspend(chord, quality, suspended)?);
+            quality = suspended;
+            rest = &rest[sus.len()..];
+            continue;
+        }
+        let (items, next) = if let Some(list) = rest.strip_prefix('(') {
+            let end = list.find(')').ok_or_else(|| ParseChordError::new(chord, "unclosed parenthesis"))?;
+            (&list[..end], &list[end + 1..])
+        } else {
+            let first_len = rest.chars().next().map_or(0, char::len_utf8);
+            let end = rest[first_len..].find(['b', '♭', '#', '♯', '(', 'a', 'n', 's'])
+                .map_or(rest.len(), |i| i + first_len);
+            (&rest[..end], &rest[end..])
+        };
+        for item in items.split(',').map(str::trim) {
+            if let Some(omitted) = item.strip_prefix("no") {
+                omissions.push(omitted.parse()?);
+            } else {
+                extensions.push(item.strip_prefix("add").unwrap_or(item).parse()?);
+            }
+        }
+        rest = next;
+    }
+    Ok((quality, extensions, omissions))
+}
+
+// suspensions after another suffix like "C9sus4", the first matching one is used
+const SUSPENSIONS: [(&str, ChordQuality); 3] = [
+    ("sus2", ChordQuality::Sus2),
+    ("sus4", ChordQuality::Sus4),
+    ("sus", ChordQuality::Sus4),
+];
+
+/// The tones of the quality, which remain as extensions, if its third is replaced by a suspended one.
+fn suspend(chord: &str, quality: ChordQuality, suspended: ChordQuality) -> Result<Vec<Degree>, ParseChordError> {
+    let intervals = quality.intervals();
+    if !intervals.contains(&3) && !intervals.contains(&4) {
+        return Err(ParseChordError::new(chord, "only a chord with a third can be suspended"));
+    }
+    Ok(intervals.iter()
+        .filter(|&&interval| interval != 3 && interval != 4 && !suspended.intervals().contains(&interval))
+        .map(|&interval| Degree::from_semitones(interval))
+        .collect())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseChordError {
+    pub input: String,
+    pub reason: &'static str,
+}
+
+impl ParseChordError {
+    fn new(input: &str, reason: &'static str) -> Self {
+        Self { input: input.to_string(), reason }
+    }
+}
+
+impl fmt::Display for ParseChordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid chord {:?}: {}", self.input, self.reason)
+    }
+}
+
+impl Error for ParseChordError {}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(s: &str) -> Chord {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_harte_syntax() {
+        assert_eq!(chord("N"), Chord::NoChord);
+        assert_eq!(chord("C:maj"), Chord::new(0, ChordQuality::Major));
+        assert_eq!(chord("C"), Chord::new(0, ChordQuality::Major));
+        assert_eq!(chord("C:maj7/3"), Chord::new(0, ChordQuality::Major7).with_bass(4));
+        assert_eq!(chord("Bb:min7/b7"), Chord::new(10, ChordQuality::Minor7).with_bass(8));
+        assert_eq!(chord("Db:(1,5)"), Chord::new(1, ChordQuality::Power));
+        assert_eq!(chord("A:(1,b3,5)"), Chord::new(9, ChordQuality::Minor));
+        assert_eq!(chord("G:9"), Chord::new(7, ChordQuality::Dominant7).with_extension(Degree::NINTH));
+        assert_eq!(chord("G:7(9)"), chord("G:9"));
+        assert_eq!(chord("F#:hdim7"), Chord::new(6, ChordQuality::HalfDiminished7));
+
+        let omitted_third = chord("E:maj(*3)");
+        assert_eq!(omitted_third.pitch_classes(), vec![4, 11]);
+        assert_eq!(omitted_third.to_string(), "E:maj(*3)");
+        assert_eq!(chord("C:(1)").pitch_classes(), vec![0]);
+
+        assert!("H:maj".parse::<Chord>().is_err());
+        assert!("C:foo".parse::<Chord>().is_err());
+        assert!("C:maj(14)".parse::<Chord>().is_err());
+    }
+
+    #[test]
+    fn parses_lead_sheet_syntax() {
+        assert_eq!(chord("Cmaj7/E"), chord("C:maj7/3"));
+        assert_eq!(chord("F#m7b5"), chord("F#:hdim7"));
+        assert_eq!(chord("Am"), chord("A:min"));
+        assert_eq!(chord("Bbm7"), chord("Bb:min7"));
+        assert_eq!(chord("Ebdim7"), chord("Eb:dim7"));
+        assert_eq!(chord("G7b9"), Chord::new(7, ChordQuality::Dominant7).with_extension(Degree::new(9, -1)));
+        assert_eq!(chord("G7(b9,#11)").pitch_classes(), vec![1, 2, 5, 7, 8, 11]);
+        assert_eq!(chord("Cadd9").pitch_classes(), vec![0, 2, 4, 7]);
+        assert_eq!(chord("C/G"), Chord::new(0, ChordQuality::Major).with_bass(7));
+        assert_eq!(chord("D5"), Chord::new(2, ChordQuality::Power));
+        assert_eq!(chord("N.C."), Chord::NoChord);
+        assert_eq!(chord("Cm7♭9"), chord("C:min7(b9)"));
+        assert_eq!(chord("F♯7♯11"), chord("F#:7(#11)"));
+    }
+
+    #[test]
+    fn parses_extended_lead_sheet_chords() {
+        assert_eq!(chord("C6/9"), chord("C:maj6(9)"));
+        assert_eq!(chord("C6/9").pitch_classes(), vec![0, 2, 4, 7, 9]);
+        assert_eq!(chord("C6/9/E"), chord("C:maj6(9)/3"));
+        assert_eq!(chord("Am6/9"), chord("A:min6(9)"));
+        assert_eq!(chord("C7sus4"), chord("C:sus4(b7)"));
+        assert_eq!(chord("C7sus4").pitch_classes(), vec![0, 5, 7, 10]);
+        assert_eq!(chord("C11"), chord("C:7(9,11)"));
+        assert_eq!(chord("C11").pitch_classes(), vec![0, 2, 4, 5, 7, 10]);
+        assert_eq!(chord("C13"), chord("C:7(9,13)"));
+        assert_eq!(chord("C13").pitch_classes(), vec![0, 2, 4, 7, 9, 10]);
+        assert_eq!(chord("Dm11"), chord("D:min7(9,11)"));
+        assert_eq!(chord("Dm11").pitch_classes(), vec![0, 2, 4, 5, 7, 9]);
+        assert_eq!(chord("C9sus4"), chord("C:sus4(b7,9)"));
+        assert_eq!(chord("C9sus4").pitch_classes(), vec![0, 2, 5, 7, 10]);
+        assert_eq!(chord("C7sus"), chord("C7sus4"));
+        assert_eq!(chord("Cmaj7sus2"), chord("C:sus2(7)"));
+        assert_eq!(chord("C13sus4/G"), chord("C:sus4(b7,9,13)/5"));
+        assert_eq!(chord("C7b9sus4"), chord("C:sus4(b7,b9)"));
+        assert_eq!(chord("C(add9)"), chord("Cadd9"));
+        assert_eq!(chord("Cm(add9)"), chord("Cmadd9"));
+    }
+
+    #[test]
+    fn unsupported_lead_sheet_notation_is_an_error() {
+        let unsupported = ["C7alt", "C(omit3)", "Csus4sus2", "C5sus4", "IV", "Hm"];
+        for &text in &unsupported {
+            assert!(text.parse::<Chord>().is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn invalid_text_is_an_error() {
+        let invalid = ["", "C/", "C:", "C:(", "Cm7(", "C♭♭(", "Cm7€", "C7é9", "Cx", "C:maj/0", "C/H", "Cadd"];
+        for &text in &invalid {
+            assert!(text.parse::<Chord>().is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn formats_and_round_trips() {
+        let labels = ["N", "C:maj", "A:min7/b3", "F#:hdim7", "G:7(b9,#11)", "E:maj(*3)", "A#:sus4/5", "D:maj/b7", "C#:minmaj7"];
+        for &label in &labels {
+            assert_eq!(chord(label).to_string(), label);
+            assert_eq!(chord(&chord(label).lead_sheet()), chord(label), "{}", chord(label).lead_sheet());
+        }
+        assert_eq!(chord("A:min7/b3").lead_sheet(), "Am7/C");
+        assert_eq!(chord("C:maj(9)").lead_sheet(), "Cadd9");
+        assert_eq!(Chord::NoChord.lead_sheet(), "N.C.");
+    }
+
+    #[test]
+    fn parenthesized_extensions_keep_the_triad() {
+        assert_eq!(chord("C(9)"), chord("C:maj(9)"));
+        assert_eq!(chord("C(9)").pitch_classes(), vec![0, 2, 4, 7]);
+        assert_eq!(chord("C(b9)"), chord("C:maj(b9)"));
+        assert_eq!(chord("C(#11)"), chord("C:maj(#11)"));
+        assert_eq!(chord("C(9)/E"), chord("C:maj(9)/3"));
+        assert_eq!(chord("C(no3)"), chord("C:maj(*3)"));
+    }
+
+    #[test]
+    fn lead_sheet_round_trips() {
+        let degrees = [Degree::new(9, -1), Degree::NINTH, Degree::new(9, 1), Degree::ELEVENTH, Degree::new(11, 1), Degree::new(13, -1), Degree::THIRTEENTH];
+        let mut extension_sets: Vec<Vec<Degree>> = vec![vec![]];
+        for (i, &first) in degrees.iter().enumerate() {
+            extension_sets.push(vec![first]);
+            extension_sets.extend(degrees[i + 1..].iter().map(|&second| vec![first, second]));
+        }
+        for &quality in ChordQuality::ALL.iter() {
+            for extensions in &extension_sets {
+                for &bass in &[0, 4, 10] {
+                    let expected = extensions.iter().fold(Chord::new(3, quality), |chord, &degree| chord.with_extension(degree)).with_bass(3 + bass);
+                    let lead_sheet = expected.lead_sheet();
+                    assert_eq!(lead_sheet.parse::<Chord>(), Ok(expected.clone()), "{} {}", lead_sheet, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn chord_tones() {
+        assert_eq!(chord("C:maj7/3").pitch_classes(), vec![0, 4, 7, 11]);
+        assert_eq!(chord("D:maj/b7").pitch_classes(), vec![0, 2, 6, 9]);
+        assert_eq!(Chord::NoChord.pitch_classes(), Vec::<usize>::new());
+        assert_eq!(Degree::new(13, -1).semitones(), 20);
+        assert_eq!(Degree::new(11, 1).semitones(), 18);
+        assert_eq!("bb7".parse::<Degree>().map(|degree| (degree.number(), degree.alteration())), Ok((7, -2)));
+        assert!("0".parse::<Degree>().is_err());
+    }
+
+    #[test]
+    fn transposition() {
+        assert_eq!(chord("A:min7/b3").transposed(5), chord("D:min7/b3"));
+        assert_eq!(chord("G:7(b9)").transposed(12), chord("G:7(b9)"));
+        assert_eq!(Chord::NoChord.transposed(3), Chord::NoChord);
+    }
+}
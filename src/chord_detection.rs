@@ -1,71 +1,541 @@
-use gromagram::Gromagram;
-use rusty_machine::learning::naive_bayes::{self, NaiveBayes};
-use rusty_machine::linalg::{Matrix, BaseMatrix};
-use rusty_machine::learning::SupModel;
-use rusty_machine::prelude::BaseMatrixMut;
-use std::fmt::Debug;
+use chord::Transpose;
+use classifiers::{Classifier, NaiveBayesClassifier};
+use gromagram::{Gromagram, GromagramInitProps};
+use lab::Segment;
+use sample::Sample;
+use rusty_machine::learning::LearningResult;
+use rusty_machine::learning::error::{Error as LearningError, ErrorKind};
+use rusty_machine::linalg::{Matrix};
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt::{self, Debug, Display};
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+/// First line of a saved model, followed by the format version.
+const MODEL_FILE_HEADER: &str = "chord-detector-model";
+const MODEL_FILE_VERSION: u32 = 2;
 
 
 pub struct ChordDetector<L> {
     gromagram: Gromagram,
     labels: Vec<L>,
     training_input: Vec<f64>,
-    training_labels: Vec<f64>,
-    model: NaiveBayes<naive_bayes::Gaussian>,
+    // index into labels for every row of training_input
+    training_labels: Vec<usize>,
+    classifier: Box<dyn Classifier>,
+    min_probability: f64,
 }
 
 impl<L> ChordDetector<L> where
     L: Clone + Eq + Debug
 {
+    /// Creates a detector with a Gaussian naive Bayes classifier.
     pub fn new(gromagram: Gromagram, labels: &[L]) -> Self {
         ChordDetector {
             gromagram,
             training_input: Vec::new(),
             training_labels: Vec::new(),
             labels: labels.to_vec(),
-            model: NaiveBayes::new(),
+            classifier: Box::new(NaiveBayesClassifier::default()),
+            min_probability: 0.0,
+        }
+    }
+
+    /// Replaces the classifier, e.g. by a `NearestTemplateClassifier`.
+    /// `finish_training` has to be called again afterwards.
+    pub fn with_classifier<C: Classifier + 'static>(self, classifier: C) -> Self {
+        Self { classifier: Box::new(classifier), ..self }
+    }
+
+    /// Chords with a lower probability are rejected, so that `detect` returns None ("no chord")
+    /// if the model isn't sure enough.
+    pub fn with_min_probability(self, min_probability: f64) -> Self {
+        Self { min_probability, ..self }
+    }
+
+    pub fn train<S: Sample>(&mut self, wav: &[S], label: &L) {
+        let label_i = self.label_index(label);
 
+        eprintln!("label = {:?}", label);
+        for features in self.training_features(wav) {
+            self.training_input.extend(&features);
+            self.training_labels.push(label_i);
         }
+        eprintln!("End Train");
     }
 
-    pub fn train(&mut self, wav: &[i16], label: &L) {
-        let label_encoding = &self.create_label_encoding(label);
+    /// Trains with a whole recording, e.g. a song, annotated by labeled segments, see `lab::read_lab`.
+    /// Only frames, which lie completely inside of a segment, are used.
+    /// Labels, which are missing in the labels of the detector, are added, unless all their frames are silent.
+    pub fn train_annotated<S: Sample>(&mut self, wav: &[S], segments: &[Segment<L>]) {
+        let sample_rate = self.gromagram.props.sample_rate;
+        for segment in segments {
+            let (start, end) = segment.sample_range(sample_rate, wav.len());
+            let segment_features = self.training_features(&wav[start..end]);
+            if segment_features.is_empty() {
+                continue;
+            }
+            let label_i = self.label_index_or_insert(&segment.label);
+            for features in segment_features {
+                self.training_input.extend(&features);
+                self.training_labels.push(label_i);
+            }
+        }
+    }
 
-        let mut gromagram = &mut self.gromagram;
-        let gromagram_len = gromagram.gromagram.len();
+    /// Normalized gromagrams of the non silent frames.
+    fn training_features<S: Sample>(&mut self, wav: &[S]) -> Vec<Vec<f64>> {
+        let gromagram = &mut self.gromagram;
 
         let step_size = gromagram.props.window_size / 4;
         let frames = wav.windows(gromagram.props.window_size).enumerate().filter(|&(i, _)| i % step_size == 0);
 
-        eprintln!("label = {:?}", label);
+        let mut features = Vec::new();
         for (_, frame) in frames {
             gromagram.reset();
             gromagram.process_audio_frame(frame);
+            if gromagram.is_silent() {
+                continue;
+            }
             gromagram.normalize();
-
-            self.training_input.extend(&gromagram.gromagram);
-            self.training_labels.extend_from_slice(&label_encoding);
+            features.push(gromagram.gromagram.clone());
         }
-        eprintln!("End Train");
+        features
     }
 
-    pub fn finish_training(&mut self) {
+    /// Trains the classifier with all recordings, fails e.g. if a label has too few examples for the classifier.
+    pub fn finish_training(&mut self) -> LearningResult<()> {
         let input_matrix: Matrix<f64> = self.training_input.chunks(self.gromagram.gromagram.len()).collect();
-        let label_matrix: Matrix<f64> = self.training_labels.chunks(self.labels.len()).collect();
-        self.model.train(&input_matrix, &label_matrix).unwrap();
+        self.classifier.train(&input_matrix, &self.training_labels, self.labels.len())
+    }
+
+    /// The most probable chord, or None ("no chord") if it's rejected by `detect_scored`.
+    pub fn detect(&mut self, gromagram: &[f64]) -> Option<L> {
+        self.detect_scored(gromagram, 1).into_iter().next().map(|(label, _)| label)
+    }
+
+    /// The (at most) k most probable labels with their posterior probabilities, in descending order.
+    /// Labels below the minimum probability are left out, so the result is empty ("no chord")
+    /// for a silent (all zero) or invalid gromagram, an untrained model or an unsure prediction.
+    /// A gromagram is invalid, if it contains non finite values or has another size than the training gromagrams.
+    pub fn detect_scored(&mut self, gromagram: &[f64], k: usize) -> Vec<(L, f64)> {
+        if gromagram.len() != self.gromagram.gromagram.len() {
+            return Vec::new();
+        }
+        if gromagram.iter().all(|&x| x == 0.0) || !gromagram.iter().all(|x| x.is_finite()) {
+            return Vec::new();
+        }
+        let probabilities = match self.classifier.probabilities(gromagram) {
+            Some(probabilities) => probabilities,
+            None => return Vec::new(),
+        };
+        let mut scored: Vec<(usize, f64)> = probabilities.into_iter().enumerate()
+            .filter(|&(_, probability)| probability >= self.min_probability)
+            .collect();
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        scored.into_iter().take(k).map(|(i, probability)| (self.labels[i].clone(), probability)).collect()
+    }
+
+    fn label_index(&self, label: &L) -> usize {
+        self.labels.iter().position(|x| x == label).unwrap()
+    }
+
+    fn label_index_or_insert(&mut self, label: &L) -> usize {
+        match self.labels.iter().position(|x| x == label) {
+            Some(label_i) => label_i,
+            None => {
+                self.labels.push(label.clone());
+                self.labels.len() - 1
+            }
+        }
+    }
+}
+
+/// Training with transposed copies of the recordings.
+impl<L> ChordDetector<L> where
+    L: Clone + Eq + Debug + Transpose
+{
+    /// Trains the label and its transpositions into the 11 other keys with one recording,
+    /// by rotating every octave of the gromagram of every frame by 0 to 11 semitones.
+    /// Transposed labels, which are missing in the labels of the detector, are added.
+    /// The gromagram has to cover whole octaves, so that the rotation moves every note to the same pitch class,
+    /// otherwise nothing is trained and an error is returned.
+    pub fn train_transposed<S: Sample>(&mut self, wav: &[S], label: &L) -> LearningResult<()> {
+        if self.gromagram.props.notes_count % 12 != 0 {
+            return Err(LearningError::new(ErrorKind::InvalidParameters, "notes_count has to be a multiple of 12 for transposition"));
+        }
+        let features = self.training_features(wav);
+        for semitones in 0..12 {
+            let label_i = self.label_index_or_insert(&label.transposed(semitones));
+            for frame_features in &features {
+                let mut rotated = frame_features.clone();
+                for octave in rotated.chunks_mut(12) {
+                    octave.rotate_right(semitones);
+                }
+                self.training_input.extend(rotated);
+                self.training_labels.push(label_i);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Saving and loading of trained models.
+///
+/// A model file stores the labels, the feature settings and the parameters of the trained classifier,
+/// so the loaded model is identical to the saved one. The training data isn't saved, so `finish_training`
+/// after `load` only uses the recordings, which were trained afterwards.
+impl<L> ChordDetector<L> where
+    L: Clone + Eq + Debug + Display + FromStr
+{
+    /// Writes the labels, the feature settings of the gromagram and the parameters of the classifier.
+    /// Labels must not contain line breaks.
+    pub fn save<W: Write>(&self, writer: &mut W) -> Result<(), ModelError> {
+        writeln!(writer, "{} {}", MODEL_FILE_HEADER, MODEL_FILE_VERSION)?;
+        for (key, value) in feature_settings(&self.gromagram.props) {
+            writeln!(writer, "{} {}", key, value)?;
+        }
+
+        writeln!(writer, "labels {}", self.labels.len())?;
+        for label in &self.labels {
+            let label = label.to_string();
+            if label.contains('\n') || label.contains('\r') {
+                return Err(ModelError::InvalidFormat(format!("label {:?} contains a line break", label)));
+            }
+            writeln!(writer, "{}", label)?;
+        }
+
+        let parameters = self.classifier.parameters();
+        writeln!(writer, "classifier {} {}", self.classifier.kind(), parameters.len())?;
+        for (name, values) in parameters {
+            write!(writer, "{}", name)?;
+            for x in values {
+                write!(writer, " {}", x)?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the labels and the model by a saved model and discards the training data.
+    /// Fails if the model was saved with other feature settings than the gromagram of this detector
+    /// or by another kind of classifier.
+    pub fn load<R: BufRead>(&mut self, reader: R) -> Result<(), ModelError> {
+        let mut lines = reader.lines();
+        let mut next_line = || -> Result<String, ModelError> {
+            lines.next().unwrap_or_else(|| Err(io::Error::new(io::ErrorKind::UnexpectedEof, "model file ended early")))
+                .map_err(ModelError::from)
+        };
+
+        let header = next_line()?;
+        let version = match header.split_whitespace().collect::<Vec<_>>()[..] {
+            [MODEL_FILE_HEADER, version] => parse::<u32>(version)?,
+            _ => return Err(ModelError::InvalidFormat(format!("invalid header {:?}", header))),
+        };
+        if version != MODEL_FILE_VERSION {
+            return Err(ModelError::UnsupportedVersion(version));
+        }
+
+        for (key, expected) in feature_settings(&self.gromagram.props) {
+            let line = next_line()?;
+            let actual = expect_key(&line, key)?;
+            if actual != expected {
+                return Err(ModelError::FeatureMismatch { setting: key, expected, actual: actual.to_string() });
+            }
+        }
+
+        let num_labels = parse::<usize>(expect_key(&next_line()?, "labels")?)?;
+        let mut labels = Vec::with_capacity(num_labels);
+        for _ in 0..num_labels {
+            let label = next_line()?;
+            labels.push(label.parse::<L>().map_err(|_| ModelError::InvalidFormat(format!("invalid label {:?}", label)))?);
+        }
+
+        let classifier_line = next_line()?;
+        let (kind, num_parameters) = match expect_key(&classifier_line, "classifier")?.split(' ').collect::<Vec<_>>()[..] {
+            [kind, num_parameters] => (kind, parse::<usize>(num_parameters)?),
+            _ => return Err(ModelError::InvalidFormat(format!("invalid classifier line {:?}", classifier_line))),
+        };
+        if kind != self.classifier.kind() {
+            return Err(ModelError::ClassifierMismatch { expected: self.classifier.kind(), actual: kind.to_string() });
+        }
+
+        let mut parameters = Vec::new();
+        for _ in 0..num_parameters {
+            let line = next_line()?;
+            let mut values = line.split(' ');
+            let name = values.next().unwrap_or("").to_string();
+            parameters.push((name, values.map(parse::<f64>).collect::<Result<Vec<_>, _>>()?));
+        }
+        self.classifier.set_parameters(&parameters, self.gromagram.gromagram.len(), labels.len())
+            .map_err(|error| ModelError::InvalidFormat(format!("invalid {} parameters: {}", kind, error)))?;
+
+        self.labels = labels;
+        self.training_input.clear();
+        self.training_labels.clear();
+        Ok(())
+    }
+}
+
+/// The gromagram settings, which change the features.
+fn feature_settings(props: &GromagramInitProps) -> Vec<(&'static str, String)> {
+    vec![
+        ("window_size", props.window_size.to_string()),
+        ("sample_rate", props.sample_rate.to_string()),
+        ("start_note", props.start_note.to_string()),
+        ("notes_count", props.notes_count.to_string()),
+        ("auto_tuning", props.auto_tuning.to_string()),
+        ("window", format!("{:?}", props.window)),
+        ("sliding", props.sliding.to_string()),
+        ("multi_resolution", props.multi_resolution.to_string()),
+        ("bins_per_semitone", props.bins_per_semitone.to_string()),
+        ("semitone_reduction", format!("{:?}", props.semitone_reduction)),
+    ]
+}
+
+fn expect_key<'a>(line: &'a str, key: &str) -> Result<&'a str, ModelError> {
+    match line.split_once(' ') {
+        Some((k, value)) if k == key => Ok(value),
+        _ => Err(ModelError::InvalidFormat(format!("expected {:?} in line {:?}", key, line))),
     }
+}
+
+fn parse<T: FromStr>(value: &str) -> Result<T, ModelError> {
+    value.parse().map_err(|_| ModelError::InvalidFormat(format!("invalid number {:?}", value)))
+}
+
+#[derive(Debug)]
+pub enum ModelError {
+    Io(io::Error),
+    InvalidFormat(String),
+    UnsupportedVersion(u32),
+    /// The model was trained with other gromagram settings than the detector uses
+    FeatureMismatch { setting: &'static str, expected: String, actual: String },
+    /// The model was saved by another kind of classifier than the detector uses
+    ClassifierMismatch { expected: &'static str, actual: String },
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ModelError::Io(ref error) => write!(f, "Could not read or write model: {}", error),
+            ModelError::InvalidFormat(ref reason) => write!(f, "Invalid model file: {}", reason),
+            ModelError::UnsupportedVersion(version) => write!(f, "Unsupported model file version {}", version),
+            ModelError::FeatureMismatch { setting, ref expected, ref actual } =>
+                write!(f, "Model was trained with {} {}, but the detector uses {}", setting, actual, expected),
+            ModelError::ClassifierMismatch { expected, ref actual } =>
+                write!(f, "Model was saved by a {} classifier, but the detector uses {}", actual, expected),
+        }
+    }
+}
+
+impl Error for ModelError {}
+
+impl From<io::Error> for ModelError {
+    fn from(error: io::Error) -> Self {
+        ModelError::Io(error)
+    }
+}
+
 
-    pub fn detect(&mut self, gromagram: &[f64]) -> L {
-        let m = Matrix::new(1, gromagram.len(), gromagram);
-        let prediction_matrix = self.model.predict(&m).unwrap();
-        let label_i = prediction_matrix.data().iter().position(|&x| x > 0.9).unwrap();
-        self.labels[label_i].clone()
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use chord::Chord;
+    use classifiers::{GaussianMixtureClassifier, NearestTemplateClassifier};
+    use lab;
+    use super::*;
+
+    fn chord(frequencies: &[f64]) -> Vec<f32> {
+        (0..8192)
+            .map(|i| frequencies.iter().map(|f| (2.0 * PI * f * i as f64 / 44_100.0).sin() * 0.2).sum::<f64>() as f32)
+            .collect()
     }
 
-    fn create_label_encoding(&self, label: &L) -> Vec<f64> {
-        let mut v = vec![0.0; self.labels.len()];
-        let label_i = self.labels.iter().position(|x| x == label).unwrap();
-        v[label_i] = 1.0;
-        v
+    fn a_major() -> Vec<f32> {
+        chord(&[220.0, 277.18, 329.63])
+    }
+
+    fn e_major() -> Vec<f32> {
+        chord(&[164.81, 207.65, 246.94])
+    }
+
+    fn gromagram() -> Gromagram {
+        Gromagram::new(GromagramInitProps { window_size: 4096, start_note: 45, notes_count: 24, ..Default::default() })
+    }
+
+    // normalized gromagram of the first window of the recording
+    fn features(wav: &[f32]) -> Vec<f64> {
+        let mut gromagram = gromagram();
+        gromagram.process_audio_frame(&wav[..4096]);
+        gromagram.normalize();
+        gromagram.gromagram
+    }
+
+    fn labels() -> Vec<Chord> {
+        vec!["A:maj".parse().unwrap(), "E:maj".parse().unwrap()]
+    }
+
+    // a detector, which has learned A major and E major
+    fn trained_detector<C: Classifier + 'static>(classifier: C) -> ChordDetector<Chord> {
+        let labels = labels();
+        let mut detector = ChordDetector::new(gromagram(), &labels).with_classifier(classifier);
+        detector.train(&a_major(), &labels[0]);
+        detector.train(&e_major(), &labels[1]);
+        detector.finish_training().unwrap();
+        detector
+    }
+
+    fn saved(detector: &ChordDetector<Chord>) -> Vec<u8> {
+        let mut file = Vec::new();
+        detector.save(&mut file).unwrap();
+        file
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut trained = trained_detector(NaiveBayesClassifier::default());
+        let file = saved(&trained);
+        // header, feature settings, labels and classifier parameters, the size doesn't depend on the training frames
+        assert_eq!(file.split(|&b| b == b'\n').filter(|line| !line.is_empty()).count(), 19);
+
+        let mut loaded = ChordDetector::<Chord>::new(gromagram(), &[]);
+        loaded.load(&file[..]).unwrap();
+        assert_eq!(saved(&loaded), file);
+        let e_major = features(&e_major());
+        assert_eq!(loaded.detect(&e_major), Some(labels()[1].clone()));
+        assert_eq!(loaded.detect(&e_major), trained.detect(&e_major));
+    }
+
+    #[test]
+    fn save_and_load_mixture_model() {
+        let mut trained = trained_detector(GaussianMixtureClassifier::default());
+        let mut loaded = ChordDetector::<Chord>::new(gromagram(), &[]).with_classifier(GaussianMixtureClassifier::default());
+        loaded.load(&saved(&trained)[..]).unwrap();
+        // the mixture is initialized randomly, so retraining would give other probabilities
+        let a_major = features(&a_major());
+        assert_eq!(loaded.detect_scored(&a_major, 2), trained.detect_scored(&a_major, 2));
+    }
+
+    #[test]
+    fn refuses_other_feature_settings() {
+        let file = saved(&trained_detector(NaiveBayesClassifier::default()));
+
+        let other_window = Gromagram::new(GromagramInitProps { window_size: 2048, start_note: 45, notes_count: 24, ..Default::default() });
+        match ChordDetector::<Chord>::new(other_window, &[]).load(&file[..]) {
+            Err(ModelError::FeatureMismatch { setting: "window_size", .. }) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        let other_notes = Gromagram::new(GromagramInitProps { window_size: 4096, start_note: 45, notes_count: 36, ..Default::default() });
+        match ChordDetector::<Chord>::new(other_notes, &[]).load(&file[..]) {
+            Err(ModelError::FeatureMismatch { setting: "notes_count", ref expected, ref actual }) if expected == "36" && actual == "24" => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        let mut other_classifier = ChordDetector::<Chord>::new(gromagram(), &[]).with_classifier(NearestTemplateClassifier::default());
+        match other_classifier.load(&file[..]) {
+            Err(ModelError::ClassifierMismatch { expected: "nearest-template", .. }) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        let newer_version = String::from_utf8(file).unwrap().replacen("model 2", "model 3", 1);
+        match ChordDetector::<Chord>::new(gromagram(), &[]).load(newer_version.as_bytes()) {
+            Err(ModelError::UnsupportedVersion(3)) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn scored_detection() {
+        assert_eq!(ChordDetector::<Chord>::new(gromagram(), &labels()).detect_scored(&[1.0; 24], 2), vec![]);
+
+        let mut detector = trained_detector(NaiveBayesClassifier::default());
+        let a_major = features(&a_major());
+        let scored = detector.detect_scored(&a_major, 2);
+        assert_eq!(scored.len(), 2);
+        assert_eq!(scored[0].0, labels()[0]);
+        assert!(scored[0].1 > scored[1].1);
+        assert!((scored[0].1 + scored[1].1 - 1.0).abs() < 1e-9);
+        assert_eq!(detector.detect_scored(&a_major, 1).len(), 1);
+        assert_eq!(detector.detect_scored(&[0.0; 24], 2), vec![]);
+
+        let mut detector = detector.with_min_probability((scored[0].1 + scored[1].1) / 2.0);
+        assert_eq!(detector.detect_scored(&a_major, 2), vec![scored[0].clone()]);
+        let mut detector = detector.with_min_probability(1.5);
+        assert_eq!(detector.detect(&a_major), None);
+    }
+
+    #[test]
+    fn gromagram_of_other_size_is_no_chord() {
+        let mut detector = trained_detector(NaiveBayesClassifier::default());
+        let a_major = features(&a_major());
+        assert_eq!(detector.detect_scored(&a_major[..23], 2), vec![]);
+        assert_eq!(detector.detect_scored(&[&a_major[..], &[0.0]].concat(), 2), vec![]);
+    }
+
+    #[test]
+    fn other_classifier() {
+        let mut detector = trained_detector(NearestTemplateClassifier::default());
+        assert_eq!(detector.detect(&features(&e_major()[4096..])), Some(labels()[1].clone()));
+    }
+
+    #[test]
+    fn too_few_examples_for_classifier() {
+        let labels = labels();
+        let mut detector = ChordDetector::new(gromagram(), &labels[..1]).with_classifier(GaussianMixtureClassifier::new(10));
+        detector.train(&a_major(), &labels[0]);
+        assert!(detector.finish_training().is_err());
+        assert_eq!(detector.detect(&[1.0; 24]), None);
+    }
+
+    #[test]
+    fn transposed_training() {
+        // every note in both octaves of the gromagram, like the overtones of real instruments
+        let octaves = |frequencies: &[f64]| chord(&frequencies.iter().flat_map(|&f| vec![f, f * 2.0]).collect::<Vec<_>>());
+        let a: Chord = "A".parse().unwrap();
+        let mut detector = ChordDetector::new(gromagram(), ::std::slice::from_ref(&a));
+        detector.train_transposed(&octaves(&[110.0, 138.59, 164.81]), &a).unwrap();
+        detector.train_transposed(&octaves(&[110.0, 130.81, 164.81]), &"Am".parse().unwrap()).unwrap();
+        detector.finish_training().unwrap();
+        assert_eq!(detector.labels.len(), 24);
+        assert_eq!(detector.labels[0], a);
+
+        let tests = [
+            ("E:maj", [164.81, 207.65, 123.47]),
+            ("C:maj", [130.81, 164.81, 196.0]),
+            ("D:min", [146.83, 174.61, 220.0]),
+            ("F#:min", [185.0, 220.0, 138.59]),
+        ];
+        for &(expected, ref frequencies) in &tests {
+            assert_eq!(detector.detect(&features(&octaves(frequencies))), Some(expected.parse().unwrap()), "{}", expected);
+        }
+    }
+
+    #[test]
+    fn transposition_needs_whole_octaves() {
+        let gromagram = Gromagram::new(GromagramInitProps { window_size: 4096, start_note: 45, notes_count: 18, ..Default::default() });
+        let mut detector = ChordDetector::new(gromagram, &[]);
+        assert!(detector.train_transposed(&a_major(), &labels()[0]).is_err());
+        assert!(detector.labels.is_empty() && detector.training_labels.is_empty());
+    }
+
+    #[test]
+    fn annotated_training() {
+        let second = 44_100;
+        let song: Vec<f32> = a_major().into_iter().cycle().take(second)
+            .chain(e_major().into_iter().cycle().take(second))
+            .chain(vec![0.0; second / 2])
+            .collect();
+        let segments: Vec<Segment<Chord>> = lab::read_lab("0.0 1.0 A:maj\n1.0 2.0 E:maj\n2.0 2.5 N\n".as_bytes()).unwrap();
+
+        let mut detector = ChordDetector::new(gromagram(), &[]);
+        detector.train_annotated(&song, &segments);
+        detector.finish_training().unwrap();
+        assert_eq!(detector.labels, labels());
+        // 40 frames of 4096 samples with a step size of 1024 fit into every chord, the silence is skipped
+        assert_eq!(detector.training_labels, [vec![0; 40], vec![1; 40]].concat());
+        assert_eq!(detector.detect(&features(&song[second..])), Some(labels()[1].clone()));
     }
 }
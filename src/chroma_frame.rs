@@ -0,0 +1,36 @@
+/// A chroma vector computed by iterating over a whole signal,
+/// e.g. with `Chromagram::frames` or `Gromagram::frames`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChromaFrame {
+    /// Index of the first sample of the hop, which completed this chroma
+    pub start_sample: usize,
+    /// `start_sample` in seconds
+    pub time_secs: f64,
+    /// Effective centre of the analysis window in seconds,
+    /// negative for the first frames, whose window starts before the signal.
+    /// With per-note windows (`GromagramInitProps::multi_resolution`) it's the centre of the mean window size.
+    pub center_secs: f64,
+    /// Centre of the analysis window of every bin of `chroma` in seconds. All are `center_secs`,
+    /// unless every note has its own window, which is centred earlier for bass notes and later for treble notes.
+    pub bin_centers_secs: Vec<f64>,
+    pub chroma: Vec<f64>,
+    /// 12 bin chroma of the bass range, all zero if no bass range is configured
+    pub bass_chroma: Vec<f64>,
+    /// True if the analysed audio was below the silence threshold of the extractor
+    pub silent: bool,
+}
+
+impl ChromaFrame {
+    pub fn new(start_sample: usize, center_sample: f64, sample_rate: f64,
+               chroma: &[f64], bass_chroma: &[f64], silent: bool) -> Self {
+        Self {
+            start_sample,
+            time_secs: start_sample as f64 / sample_rate,
+            center_secs: center_sample / sample_rate,
+            bin_centers_secs: vec![center_sample / sample_rate; chroma.len()],
+            chroma: chroma.to_vec(),
+            bass_chroma: bass_chroma.to_vec(),
+            silent,
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use std::f64;
+use std::f64::consts::PI;
+
+use chroma_frame::ChromaFrame;
+use window::WindowFunction;
+
+const NUM_NOTES_IN_OCTAVE: usize = 12;
+
+/// Smooths a stream of feature vectors with a Hann window over the last `window_length` frames
+/// and keeps only every `downsampling`-th result.
+pub struct TemporalSmoother {
+    size: usize,
+    window: Vec<f64>,
+    // ring buffer of the last window_length frames
+    frames: Vec<f64>,
+    frame_pos: usize,
+    downsampling: usize,
+    num_frames: usize,
+    pub output: Vec<f64>,
+}
+
+impl TemporalSmoother {
+    pub fn new(size: usize, window_length: usize, downsampling: usize) -> Self {
+        assert!(window_length > 0 && downsampling > 0, "window_length and downsampling must be greater than 0");
+        Self {
+            size,
+            // symmetric Hann window without zeros at the ends
+            window: (0..window_length).map(|i| WindowFunction::Hann.value(i + 1, window_length + 1)).collect(),
+            frames: vec![0.0; size * window_length],
+            frame_pos: 0,
+            downsampling,
+            num_frames: 0,
+            output: vec![0.0; size],
+        }
+    }
+
+    /// Adds a frame and returns true if a new smoothed output has been calculated.
+    pub fn process_frame(&mut self, frame: &[f64]) -> bool {
+        assert_eq!(frame.len(), self.size);
+        let window_length = self.window.len();
+        self.frames[self.frame_pos * self.size..(self.frame_pos + 1) * self.size].copy_from_slice(frame);
+        self.frame_pos = (self.frame_pos + 1) % window_length;
+
+        let ready = self.num_frames % self.downsampling == 0;
+        self.num_frames += 1;
+        if !ready {
+            return false;
+        }
+
+        for x in &mut self.output {
+            *x = 0.0;
+        }
+        // the oldest frame is at frame_pos
+        for (i, &weight) in self.window.iter().enumerate() {
+            let pos = (self.frame_pos + i) % window_length;
+            for (x, &value) in self.output.iter_mut().zip(&self.frames[pos * self.size..(pos + 1) * self.size]) {
+                *x += weight * value;
+            }
+        }
+        true
+    }
+
+    pub fn reset(&mut self) {
+        for x in &mut self.frames {
+            *x = 0.0;
+        }
+        self.frame_pos = 0;
+        self.num_frames = 0;
+    }
+}
+
+/// Chroma Energy Normalized Statistics (Müller et al., 2005).
+#[derive(Clone, Debug)]
+pub struct CensInitProps {
+    /// Number of chroma bins
+    pub size: usize,
+    /// A bin of the L1 normalized chroma is quantized to the number of thresholds it reaches
+    pub quantization_thresholds: Vec<f64>,
+    /// Length of the Hann smoothing window in frames
+    pub window_length: usize,
+    /// Only every downsampling-th smoothed frame is output
+    pub downsampling: usize,
+}
+
+impl Default for CensInitProps {
+    fn default() -> Self {
+        Self {
+            size: NUM_NOTES_IN_OCTAVE,
+            quantization_thresholds: vec![0.05, 0.1, 0.2, 0.4],
+            window_length: 41,
+            downsampling: 10,
+        }
+    }
+}
+
+/// Chroma, which is robust to dynamics and local tempo variations, because it keeps
+/// only the coarse energy distribution over the pitch classes, averaged over time.
+pub struct Cens {
+    props: CensInitProps,
+    quantized: Vec<f64>,
+    smoother: TemporalSmoother,
+    pub cens: Vec<f64>,
+}
+
+impl Cens {
+    pub fn new(props: CensInitProps) -> Self {
+        Self {
+            quantized: vec![0.0; props.size],
+            smoother: TemporalSmoother::new(props.size, props.window_length, props.downsampling),
+            cens: vec![0.0; props.size],
+            props,
+        }
+    }
+
+    /// Processes a chroma frame, e.g. of a `Chromagram`, and returns true if `cens` has been updated.
+    /// The chroma must have `size` bins.
+    pub fn process_frame(&mut self, chroma: &[f64]) -> bool {
+        assert_eq!(chroma.len(), self.props.size, "chroma must have size bins");
+        let sum: f64 = chroma.iter().map(|x| x.abs()).sum();
+        for (quantized, &x) in self.quantized.iter_mut().zip(chroma) {
+            let normalized = if sum > 0.0 { x.abs() / sum } else { 0.0 };
+            *quantized = self.props.quantization_thresholds.iter().filter(|&&threshold| normalized >= threshold).count() as f64;
+        }
+
+        if !self.smoother.process_frame(&self.quantized) {
+            return false;
+        }
+        self.cens.copy_from_slice(&self.smoother.output);
+        normalize_l2(&mut self.cens);
+        true
+    }
+
+    /// Processes a stream of chroma frames and yields a CENS frame whenever one is ready.
+    pub fn process_frames<'a, I>(&'a mut self, frames: I) -> impl Iterator<Item = ChromaFrame> + 'a
+        where I: IntoIterator<Item = ChromaFrame>, I::IntoIter: 'a {
+        frames.into_iter().filter_map(move |frame| {
+            if self.process_frame(&frame.chroma) {
+                Some(ChromaFrame { chroma: self.cens.clone(), ..frame })
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn reset(&mut self) {
+        self.smoother.reset();
+    }
+}
+
+/// Chroma DCT-Reduced log Pitch (Müller and Ewert, 2010).
+#[derive(Clone, Debug)]
+pub struct CrpInitProps {
+    /// MIDI note of the first pitch of the input, e.g. `GromagramInitProps::start_note`
+    pub start_note: usize,
+    /// The pitch energies e are compressed by log(compression * e + 1)
+    pub compression: f64,
+    /// Number of the lowest DCT coefficients, which are set to 0 to remove the timbre.
+    /// None scales the 55 of 120 pitches recommended by Müller and Ewert to the number of pitches.
+    pub num_removed_coefficients: Option<usize>,
+    /// Length of the Hann smoothing window in frames, 1 disables smoothing
+    pub window_length: usize,
+    /// Only every downsampling-th frame is output
+    pub downsampling: usize,
+}
+
+impl Default for CrpInitProps {
+    fn default() -> Self {
+        Self {
+            start_note: 0,
+            compression: 1000.0,
+            num_removed_coefficients: None,
+            window_length: 1,
+            downsampling: 1,
+        }
+    }
+}
+
+/// Chroma, which is robust to timbre, calculated from the energies of single pitches,
+/// e.g. a `Gromagram` over several octaves.
+///
+/// The lower DCT coefficients of the log compressed pitch energies describe the spectral envelope,
+/// i.e. the timbre. They are removed before the pitches are folded into chroma.
+pub struct Crp {
+    props: CrpInitProps,
+    num_pitches: usize,
+    num_removed_coefficients: usize,
+    // orthonormal DCT-II, row k is the k-th basis vector
+    dct: Vec<f64>,
+    log_pitches: Vec<f64>,
+    coefficients: Vec<f64>,
+    chroma: Vec<f64>,
+    smoother: TemporalSmoother,
+    pub crp: Vec<f64>,
+}
+
+impl Crp {
+    pub fn new(props: CrpInitProps, num_pitches: usize) -> Self {
+        let num_removed_coefficients = props.num_removed_coefficients
+            .unwrap_or_else(|| (num_pitches as f64 * 55.0 / 120.0).round() as usize);
+        assert!(num_removed_coefficients < num_pitches, "num_removed_coefficients must be less than num_pitches");
+        let mut dct = vec![0.0; num_pitches * num_pitches];
+        for (k, basis) in dct.chunks_mut(num_pitches.max(1)).enumerate() {
+            let scale = if k == 0 { (1.0 / num_pitches as f64).sqrt() } else { (2.0 / num_pitches as f64).sqrt() };
+            for (n, x) in basis.iter_mut().enumerate() {
+                *x = scale * (PI * (n as f64 + 0.5) * k as f64 / num_pitches as f64).cos();
+            }
+        }
+
+        Self {
+            num_pitches,
+            num_removed_coefficients,
+            dct,
+            log_pitches: vec![0.0; num_pitches],
+            coefficients: vec![0.0; num_pitches],
+            chroma: vec![0.0; NUM_NOTES_IN_OCTAVE],
+            smoother: TemporalSmoother::new(NUM_NOTES_IN_OCTAVE, props.window_length, props.downsampling),
+            crp: vec![0.0; NUM_NOTES_IN_OCTAVE],
+            props,
+        }
+    }
+
+    /// Processes the energies of `num_pitches` consecutive pitches and returns true if `crp` has been updated.
+    pub fn process_pitch_frame(&mut self, pitches: &[f64]) -> bool {
+        assert_eq!(pitches.len(), self.num_pitches);
+        let n = self.num_pitches;
+
+        for (log_pitch, &energy) in self.log_pitches.iter_mut().zip(pitches) {
+            *log_pitch = (self.props.compression * energy.abs() + 1.0).ln();
+        }
+        for (k, coefficient) in self.coefficients.iter_mut().enumerate() {
+            *coefficient = if k < self.num_removed_coefficients {
+                0.0
+            } else {
+                dot(&self.dct[k * n..(k + 1) * n], &self.log_pitches)
+            };
+        }
+
+        for x in &mut self.chroma {
+            *x = 0.0;
+        }
+        // the inverse of the orthonormal DCT is its transpose
+        for i in 0..n {
+            let value: f64 = self.coefficients.iter().enumerate().map(|(k, &c)| c * self.dct[k * n + i]).sum();
+            self.chroma[(self.props.start_note + i) % NUM_NOTES_IN_OCTAVE] += value;
+        }
+        normalize_l2(&mut self.chroma);
+
+        if !self.smoother.process_frame(&self.chroma) {
+            return false;
+        }
+        self.crp.copy_from_slice(&self.smoother.output);
+        normalize_l2(&mut self.crp);
+        true
+    }
+
+    /// Processes a stream of pitch frames, e.g. of `Gromagram::frames`, and yields a CRP frame whenever one is ready.
+    pub fn process_frames<'a, I>(&'a mut self, frames: I) -> impl Iterator<Item = ChromaFrame> + 'a
+        where I: IntoIterator<Item = ChromaFrame>, I::IntoIter: 'a {
+        frames.into_iter().filter_map(move |frame| {
+            if self.process_pitch_frame(&frame.chroma) {
+                Some(ChromaFrame { chroma: self.crp.clone(), ..frame })
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn reset(&mut self) {
+        self.smoother.reset();
+    }
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Normalizes to unit length, a vector close to 0 is replaced by the uniform unit vector.
+fn normalize_l2(vector: &mut [f64]) {
+    let norm = vector.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let len = vector.len() as f64;
+    for x in vector.iter_mut() {
+        *x = if norm > 1e-9 { *x / norm } else { 1.0 / len.sqrt() };
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &[f64], b: &[f64]) {
+        assert!(a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-9), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn cens_quantizes_smooths_and_downsamples() {
+        let mut cens = Cens::new(CensInitProps { window_length: 5, downsampling: 10, ..Default::default() });
+        // C major, the G is quieter
+        let mut chroma = [0.0; 12];
+        chroma[0] = 1.0;
+        chroma[4] = 1.0;
+        chroma[7] = 0.5;
+
+        let ready: Vec<bool> = (0..25).map(|i| {
+            let gain = if i % 2 == 0 { 1.0 } else { 7.0 };
+            let scaled: Vec<f64> = chroma.iter().map(|x| x * gain).collect();
+            cens.process_frame(&scaled)
+        }).collect();
+        assert_eq!(ready.iter().filter(|&&r| r).count(), 3);
+        assert!(ready[0] && ready[10] && ready[20]);
+
+        // quantized to 4, 4, 3, independent of the gain
+        let mut expected = vec![0.0; 12];
+        expected[0] = 4.0 / 41f64.sqrt();
+        expected[4] = 4.0 / 41f64.sqrt();
+        expected[7] = 3.0 / 41f64.sqrt();
+        assert_close(&cens.cens, &expected);
+    }
+
+    #[test]
+    fn crp_removes_spectral_envelope() {
+        let num_pitches = 60;
+        let mut crp = Crp::new(CrpInitProps { start_note: 36, num_removed_coefficients: Some(15), ..Default::default() }, num_pitches);
+
+        // a flat spectrum has only a DC coefficient and no pitch class information
+        crp.process_pitch_frame(&vec![0.5; num_pitches]);
+        assert_close(&crp.crp, &[1.0 / 12f64.sqrt(); 12]);
+
+        // G major (G, B, D in several octaves) over a strongly falling spectral envelope
+        let pitches: Vec<f64> = (0..num_pitches).map(|i| {
+            let envelope = 0.01 * (-(i as f64) / 10.0).exp();
+            let tone = if [7, 11, 2].contains(&((36 + i) % 12)) { 0.01 } else { 0.0 };
+            envelope + tone
+        }).collect();
+        crp.process_pitch_frame(&pitches);
+
+        let mut strongest: Vec<usize> = (0..12).collect();
+        strongest.sort_by(|&a, &b| crp.crp[b].partial_cmp(&crp.crp[a]).unwrap());
+        strongest.truncate(3);
+        strongest.sort();
+        assert_eq!(strongest, vec![2, 7, 11]);
+    }
+
+    #[test]
+    fn crp_default_scales_to_few_pitches() {
+        // two octaves of a gromagram starting at A2
+        let num_pitches = 24;
+        let mut crp = Crp::new(CrpInitProps { start_note: 45, ..Default::default() }, num_pitches);
+        assert_eq!(crp.num_removed_coefficients, 11);
+
+        // A major
+        let pitches: Vec<f64> = (0..num_pitches)
+            .map(|i| if [9, 1, 4].contains(&((45 + i) % 12)) { 0.01 } else { 0.0 })
+            .collect();
+        crp.process_pitch_frame(&pitches);
+        let mut strongest: Vec<usize> = (0..12).collect();
+        strongest.sort_by(|&a, &b| crp.crp[b].total_cmp(&crp.crp[a]));
+        strongest.truncate(3);
+        strongest.sort();
+        assert_eq!(strongest, vec![1, 4, 9], "crp = {:?}", crp.crp);
+    }
+
+    #[test]
+    #[should_panic(expected = "chroma must have size bins")]
+    fn cens_rejects_wrong_chroma_length() {
+        Cens::new(CensInitProps::default()).process_frame(&[1.0; 24]);
+    }
+}
@@ -1,5 +1,5 @@
 //=======================================================================
-/** @file chromagram.rs
+/*  @file chromagram.rs
  *  @brief Chromagram - a class for calculating the chromagram in real-time
  *  @rust-implementation Marco Stahl
  *  @copyright Copyright (C) 2018 Marco Stahl
@@ -24,178 +24,703 @@
  */
 //=======================================================================
 
+use std::error::Error;
 use std::f64;
-use std::f64::consts::PI;
-
-use rustfft::FFTplanner;
-use rustfft::num_complex::Complex;
-use rustfft::num_traits::Zero;
-
-//const C3: f64 = 130.81278265 / 2.0;
-//const C3: f64 = 55.0;
-const C3: f64 = 196.0/4.0;
-const BUFFER_SIZE: usize = 1024 * 4;
-const NUM_HARMONICS: usize = 2;
-const NUM_OCTAVES: usize = 2;
+use std::fmt;
+
+use chroma_frame::ChromaFrame;
+use decimator::Decimator;
+use hpcp::{Hpcp, HpcpInitProps};
+use nnls_chroma::{NnlsChroma, NnlsChromaInitProps};
+use normalization::Normalization;
+use sample::Sample;
+use spectrum_analyzer::SpectrumAnalyzer;
+use tuning::{Tuning, TuningEstimator, TuningEstimatorInitProps};
+use window::WindowFunction;
+
 const NUM_NOTES_IN_OCTAVE: usize = 12;
-const NUM_BINS_TO_SEARCH: isize = 2;
-const DOWN_SAMPLING_FACTOR: usize = 1;
-
-lazy_static! {
-    static ref NOTE_FREQUENCIES: Vec<f64> = (0..NUM_NOTES_IN_OCTAVE)
-        .map(|i| C3 * f64::powf (2.0, ((i as f64) / NUM_NOTES_IN_OCTAVE as f64)))
-        .collect();
-    static ref HAMMING_WINDOW: Vec<f64> = (0..BUFFER_SIZE)
-        .map(|i| 0.54 - 0.46 * f64::cos (2.0 * PI * ((i as f64) / ( BUFFER_SIZE as f64))))
-        .collect();
-}
+/// Largest deviation of an estimated tuning from the reference in cents
+const MAX_TUNING_DEVIATION: f64 = 50.0;
 
 pub struct Chromagram {
     props: ChromagramInitProps,
+    tuning: Tuning,
+    tuning_estimator: TuningEstimator,
+    note_frequencies: Vec<f64>,
     buffer: Vec<f64>,
     pub chromagram: Vec<f64>,
+    /// 12 bin chroma of the octaves below the reference frequency, index 0 = C
+    pub bass_chromagram: Vec<f64>,
+    spectrum_analyzer: SpectrumAnalyzer,
     magnitude_spectrum: Vec<f64>,
-    filtered_frame: Vec<f64>,
-    fft_in: Vec<Complex<f64>>,
-    fft_out: Vec<Complex<f64>>,
+    hpcp: Option<Hpcp>,
+    nnls_chroma: Option<NnlsChroma>,
+    decimator: Decimator,
     downsampled_input_audio_frame: Vec<f64>,
     num_samples_since_last_calculation: usize,
-    // In samples at the input audio sampling frequency
-    chroma_calculation_interval: usize,
     chroma_ready: bool,
+    silent: bool,
 }
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct ChromagramInitProps {
     pub frame_size: usize,
     pub sample_rate: usize,
+    /// Frequency of chroma bin 0, which must be a C so that the chroma is aligned to C
+    pub reference_frequency: f64,
+    /// FFT size in samples at the downsampled rate
+    pub buffer_size: usize,
+    pub num_harmonics: usize,
+    pub num_octaves: usize,
+    /// Number of octaves below the reference frequency, which are folded into the bass chromagram
+    pub num_bass_octaves: usize,
+    pub num_bins_to_search: usize,
+    pub down_sampling_factor: usize,
+    /// In samples at the input audio sampling frequency
+    pub chroma_calculation_interval: usize,
+    /// Estimate the tuning from the spectrum and retune the note frequencies accordingly
+    pub auto_tuning: bool,
+    pub chroma_method: ChromaMethod,
+    pub window: WindowFunction,
+    /// If the RMS of the analysed (downsampled) buffer is at most this threshold,
+    /// the chroma is set to 0 and the frame is flagged as silent
+    pub silence_threshold: f64,
+    /// Applied to the chromagram and the bass chromagram after every calculation
+    pub normalization: Normalization,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChromaMethod {
+    /// Sum of the maximum spectrum bins around every note, its octaves and harmonics (original algorithm)
+    MaxBin,
+    /// Harmonic Pitch Class Profile from interpolated spectral peaks, with 12, 24 or 36 bins
+    Hpcp(HpcpInitProps),
+    /// Approximate transcription by non-negative least squares, treble and bass chroma come from
+    /// the MIDI note ranges of the props instead of the reference frequency
+    Nnls(NnlsChromaInitProps),
 }
 
 impl Default for ChromagramInitProps {
     fn default() -> Self {
-        Self { frame_size: 256, sample_rate: 44_100 }
+        Self {
+            frame_size: 256,
+            sample_rate: 44_100,
+            reference_frequency: 130.81278265,
+            buffer_size: 1024 * 8,
+            num_harmonics: 2,
+            num_octaves: 2,
+            num_bass_octaves: 2,
+            num_bins_to_search: 2,
+            down_sampling_factor: 4,
+            chroma_calculation_interval: 4096,
+            auto_tuning: false,
+            chroma_method: ChromaMethod::MaxBin,
+            window: WindowFunction::Hamming,
+            silence_threshold: 0.0,
+            normalization: Normalization::None,
+        }
+    }
+}
+
+impl ChromagramInitProps {
+    pub fn new(frame_size: usize, sample_rate: usize) -> Self {
+        Self { frame_size, sample_rate, ..Default::default() }
+    }
+
+    pub fn with_reference_frequency(self, reference_frequency: f64) -> Self {
+        Self { reference_frequency, ..self }
+    }
+
+    pub fn with_buffer_size(self, buffer_size: usize) -> Self {
+        Self { buffer_size, ..self }
+    }
+
+    pub fn with_num_harmonics(self, num_harmonics: usize) -> Self {
+        Self { num_harmonics, ..self }
+    }
+
+    pub fn with_num_octaves(self, num_octaves: usize) -> Self {
+        Self { num_octaves, ..self }
+    }
+
+    pub fn with_num_bass_octaves(self, num_bass_octaves: usize) -> Self {
+        Self { num_bass_octaves, ..self }
+    }
+
+    pub fn with_num_bins_to_search(self, num_bins_to_search: usize) -> Self {
+        Self { num_bins_to_search, ..self }
+    }
+
+    pub fn with_down_sampling_factor(self, down_sampling_factor: usize) -> Self {
+        Self { down_sampling_factor, ..self }
+    }
+
+    pub fn with_chroma_calculation_interval(self, chroma_calculation_interval: usize) -> Self {
+        Self { chroma_calculation_interval, ..self }
+    }
+
+    pub fn with_auto_tuning(self, auto_tuning: bool) -> Self {
+        Self { auto_tuning, ..self }
+    }
+
+    pub fn with_chroma_method(self, chroma_method: ChromaMethod) -> Self {
+        Self { chroma_method, ..self }
+    }
+
+    pub fn with_window(self, window: WindowFunction) -> Self {
+        Self { window, ..self }
+    }
+
+    pub fn with_silence_threshold(self, silence_threshold: f64) -> Self {
+        Self { silence_threshold, ..self }
+    }
+
+    pub fn with_normalization(self, normalization: Normalization) -> Self {
+        Self { normalization, ..self }
+    }
+
+    /// Validates the props, so that they can be passed to `Chromagram::new`.
+    pub fn build(self) -> Result<Self, ChromagramError> {
+        self.validate()?;
+        Ok(self)
+    }
+
+    pub fn validate(&self) -> Result<(), ChromagramError> {
+        let invalid = |reason| Err(ChromagramError::InvalidProps(reason));
+        if self.frame_size == 0 {
+            return invalid("frame_size must be greater than 0");
+        }
+        if self.sample_rate == 0 {
+            return invalid("sample_rate must be greater than 0");
+        }
+        if !(self.reference_frequency.is_finite() && self.reference_frequency > 0.0) {
+            return invalid("reference_frequency must be a positive frequency");
+        }
+        if self.num_harmonics == 0 {
+            return invalid("num_harmonics must be greater than 0");
+        }
+        if self.num_octaves == 0 {
+            return invalid("num_octaves must be greater than 0");
+        }
+        if self.down_sampling_factor == 0 {
+            return invalid("down_sampling_factor must be greater than 0");
+        }
+        if self.frame_size % self.down_sampling_factor != 0 {
+            return invalid("frame_size must be a multiple of down_sampling_factor");
+        }
+        if self.frame_size / self.down_sampling_factor > self.buffer_size {
+            return invalid("downsampled frame_size must not exceed buffer_size");
+        }
+        if self.chroma_calculation_interval == 0 {
+            return invalid("chroma_calculation_interval must be greater than 0");
+        }
+        if !(self.silence_threshold >= 0.0 && self.silence_threshold.is_finite()) {
+            return invalid("silence_threshold must be a finite number >= 0");
+        }
+        if self.num_bass_octaves > 0 && self.bass_bin_range(0, self.num_bass_octaves, 1.0 / max_tuning_ratio()).0 < 1 {
+            return invalid("lowest bass note is below the frequency resolution, reduce num_bass_octaves or raise buffer_size");
+        }
+        match self.chroma_method {
+            ChromaMethod::MaxBin => {
+                let (min_bin, max_bin) = self.max_bin_search_range();
+                if min_bin < 0 {
+                    return invalid("lowest searched bin is below 0, raise reference_frequency or buffer_size");
+                }
+                if max_bin > (self.buffer_size / 2 + 1) as isize {
+                    return invalid("highest searched bin is above the Nyquist frequency, reduce num_octaves or num_harmonics");
+                }
+            }
+            ChromaMethod::Hpcp(ref hpcp_props) => {
+                if hpcp_props.size == 0 || hpcp_props.size % NUM_NOTES_IN_OCTAVE != 0 {
+                    return invalid("HPCP size must be a multiple of 12");
+                }
+            }
+            ChromaMethod::Nnls(ref nnls_props) => {
+                if nnls_props.bass_range.0 >= nnls_props.bass_range.1 || nnls_props.treble_range.0 >= nnls_props.treble_range.1 {
+                    return invalid("NNLS note ranges must not be empty");
+                }
+                let (start, end) = nnls_props.linear_spectrum_note_range();
+                let min_frequency = nnls_props.tuning.note_frequency(start as f64) / max_tuning_ratio();
+                let max_frequency = nnls_props.tuning.note_frequency(end as f64) * max_tuning_ratio();
+                if min_frequency / self.divisor_ratio() < 1.0 {
+                    return invalid("lowest NNLS note is below the frequency resolution, raise the note ranges or buffer_size");
+                }
+                if max_frequency / self.divisor_ratio() > (self.buffer_size / 2) as f64 {
+                    return invalid("highest NNLS note is above the Nyquist frequency, lower the note ranges or down_sampling_factor");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Smallest and largest (exclusive) spectrum bin searched by the max bin chroma method
+    /// for any tuning within +-50 cents.
+    fn max_bin_search_range(&self) -> (isize, isize) {
+        let divisor_ratio = self.divisor_ratio();
+        let min_frequency = self.reference_frequency / max_tuning_ratio();
+        let max_frequency = self.reference_frequency * max_tuning_ratio()
+            * f64::powf(2.0, (NUM_NOTES_IN_OCTAVE - 1) as f64 / NUM_NOTES_IN_OCTAVE as f64)
+            * (self.num_octaves * self.num_harmonics) as f64;
+        let min_bin = (min_frequency / divisor_ratio).round() as isize - self.num_bins_to_search as isize;
+        let max_bin = (max_frequency / divisor_ratio).round() as isize + (self.num_bins_to_search * self.num_harmonics) as isize;
+        (min_bin, max_bin)
+    }
+
+    /// Spectrum bins (inclusive) within half a semitone of the pitch class in the given octave below the reference frequency.
+    fn bass_bin_range(&self, pitch_class: usize, octave: usize, tuning_ratio: f64) -> (isize, isize) {
+        let frequency = self.reference_frequency * tuning_ratio
+            * f64::powf(2.0, pitch_class as f64 / NUM_NOTES_IN_OCTAVE as f64 - octave as f64);
+        let half_semitone = f64::powf(2.0, 0.5 / NUM_NOTES_IN_OCTAVE as f64);
+        let min_bin = (frequency / half_semitone / self.divisor_ratio()).round() as isize;
+        let max_bin = (frequency * half_semitone / self.divisor_ratio()).round() as isize;
+        (min_bin, max_bin)
+    }
+
+    /// Frequency distance of two spectrum bins
+    fn divisor_ratio(&self) -> f64 {
+        self.sample_rate as f64 / self.down_sampling_factor as f64 / self.buffer_size as f64
     }
 }
 
+fn max_tuning_ratio() -> f64 {
+    f64::powf(2.0, MAX_TUNING_DEVIATION / 1200.0)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChromagramError {
+    InvalidProps(&'static str),
+    /// An audio frame must have exactly `frame_size` samples
+    InvalidFrameLength { expected: usize, actual: usize },
+}
+
+impl fmt::Display for ChromagramError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ChromagramError::InvalidProps(reason) => write!(f, "Invalid chromagram props: {}", reason),
+            ChromagramError::InvalidFrameLength { expected, actual } =>
+                write!(f, "Invalid audio frame length {}, expected {} samples", actual, expected),
+        }
+    }
+}
+
+impl Error for ChromagramError {}
+
 impl Chromagram {
-    pub fn new(props: ChromagramInitProps) -> Self {
-        let chromagram = Self {
-            buffer: vec![0.0; BUFFER_SIZE],
-            chromagram: vec![0.0; NUM_NOTES_IN_OCTAVE],
-            magnitude_spectrum: vec![0.0; BUFFER_SIZE / 2 + 1],
-            filtered_frame: vec![0.0; props.frame_size],
-            fft_in: vec![Complex::zero(); BUFFER_SIZE],
-            fft_out: vec![Complex::zero(); BUFFER_SIZE],
-            downsampled_input_audio_frame: vec![0.0; props.frame_size / DOWN_SAMPLING_FACTOR],
+    pub fn new(props: ChromagramInitProps) -> Result<Self, ChromagramError> {
+        props.validate()?;
+
+        let buffer_size = props.buffer_size;
+        let tuning = Tuning::default();
+        let mut note_frequencies = vec![0.0; NUM_NOTES_IN_OCTAVE];
+        calculate_note_frequencies(props.reference_frequency, tuning, &mut note_frequencies);
+        let hpcp = match props.chroma_method {
+            ChromaMethod::Hpcp(ref hpcp_props) => Some(Hpcp::new(hpcp_props.clone())),
+            _ => None,
+        };
+        let nnls_chroma = match props.chroma_method {
+            ChromaMethod::Nnls(ref nnls_props) => Some(NnlsChroma::for_linear_spectrum(nnls_props.clone())),
+            _ => None,
+        };
+        let chroma_size = hpcp.as_ref().map_or(NUM_NOTES_IN_OCTAVE, |hpcp| hpcp.hpcp.len());
+
+        Ok(Self {
+            tuning,
+            tuning_estimator: TuningEstimator::new(TuningEstimatorInitProps::default()),
+            note_frequencies,
+            buffer: vec![0.0; buffer_size],
+            chromagram: vec![0.0; chroma_size],
+            bass_chromagram: vec![0.0; NUM_NOTES_IN_OCTAVE],
+            spectrum_analyzer: SpectrumAnalyzer::with_window_function(buffer_size, props.window),
+            magnitude_spectrum: vec![0.0; buffer_size / 2 + 1],
+            hpcp,
+            nnls_chroma,
+            decimator: Decimator::new(props.down_sampling_factor),
+            downsampled_input_audio_frame: vec![0.0; props.frame_size / props.down_sampling_factor],
             num_samples_since_last_calculation: 0,
-            chroma_calculation_interval: 4096,
             chroma_ready: false,
+            silent: true,
             props,
-        };
-
-        // TODO: setup fft
-
-        chromagram
+        })
     }
 
-    pub fn process_audio_frame(&mut self, input_audio_frame: &[f64]) {
-        self.chroma_ready = true;
+    /// Processes an audio frame of exactly `frame_size` samples.
+    pub fn process_audio_frame<S: Sample>(&mut self, input_audio_frame: &[S]) -> Result<(), ChromagramError> {
+        if input_audio_frame.len() != self.props.frame_size {
+            return Err(ChromagramError::InvalidFrameLength {
+                expected: self.props.frame_size,
+                actual: input_audio_frame.len(),
+            });
+        }
+
+        self.chroma_ready = false;
         self.down_sample_frame(input_audio_frame);
 
         // move samples back
-        for i in 0..(BUFFER_SIZE - self.downsampled_input_audio_frame.len()) {
+        for i in 0..(self.props.buffer_size - self.downsampled_input_audio_frame.len()) {
             self.buffer[i] = self.buffer[i + self.downsampled_input_audio_frame.len()];
         }
 
-        let mut n = 0;
-
-        // move samples back
-        for i in (BUFFER_SIZE - self.downsampled_input_audio_frame.len())..BUFFER_SIZE {
-            self.buffer[i] = self.downsampled_input_audio_frame[n];
-            n += 1;
-        }
+        // add new samples to buffer
+        let new_samples_start = self.props.buffer_size - self.downsampled_input_audio_frame.len();
+        self.buffer[new_samples_start..].copy_from_slice(&self.downsampled_input_audio_frame);
 
         self.num_samples_since_last_calculation += self.props.frame_size;
 
-        if self.num_samples_since_last_calculation >= self.chroma_calculation_interval {
+        if self.num_samples_since_last_calculation >= self.props.chroma_calculation_interval {
             self.calculate_chromagram();
             self.num_samples_since_last_calculation = 0;
         }
+        Ok(())
     }
 
+    /// True if the last call of `process_audio_frame` calculated a new chroma.
     pub fn is_ready(&self) -> bool {
         self.chroma_ready
     }
 
+    /// True if the audio of the last calculated chroma was below the silence threshold.
+    pub fn is_silent(&self) -> bool {
+        self.silent
+    }
+
+    /// Processes a whole signal and yields a chroma every `chroma_calculation_interval` samples,
+    /// rounded up to whole frames. The last incomplete frame is padded with zeros.
+    pub fn frames<S: Sample, I: IntoIterator<Item = S>>(&mut self, samples: I) -> ChromagramFrames<'_, I::IntoIter> {
+        ChromagramFrames {
+            frame: Vec::with_capacity(self.props.frame_size),
+            chromagram: self,
+            samples: samples.into_iter(),
+            position: 0,
+            hop_start: 0,
+        }
+    }
+
+    pub fn tuning(&self) -> Tuning {
+        self.tuning
+    }
+
+    /// Retunes the note frequencies, e.g. to a tuning estimated by a `TuningEstimator`.
+    pub fn set_tuning(&mut self, tuning: Tuning) {
+        self.tuning = tuning;
+        calculate_note_frequencies(self.props.reference_frequency, tuning, &mut self.note_frequencies);
+    }
+
     fn calculate_chromagram(&mut self) {
+        self.chroma_ready = true;
+        let rms = (self.buffer.iter().map(|x| x * x).sum::<f64>() / self.buffer.len() as f64).sqrt();
+        self.silent = rms <= self.props.silence_threshold;
+        if self.silent {
+            for x in self.chromagram.iter_mut().chain(self.bass_chromagram.iter_mut()) {
+                *x = 0.0;
+            }
+            return;
+        }
+
         self.calculate_magnitude_spectrum();
 
-        let divisor_ratio = self.props.sample_rate as f64 / DOWN_SAMPLING_FACTOR as f64 / BUFFER_SIZE as f64;
+        let divisor_ratio = self.props.divisor_ratio();
 
+        if self.props.auto_tuning {
+            self.tuning_estimator.process_magnitude_spectrum(&self.magnitude_spectrum, divisor_ratio);
+            let tuning = self.tuning_estimator.tuning();
+            self.set_tuning(tuning);
+        }
+
+        if let Some(ref mut hpcp) = self.hpcp {
+            hpcp.process_magnitude_spectrum(&self.magnitude_spectrum, divisor_ratio, self.note_frequencies[0]);
+            self.chromagram.copy_from_slice(&hpcp.hpcp);
+            self.calculate_bass_chromagram();
+        } else if let Some(ref mut nnls_chroma) = self.nnls_chroma {
+            nnls_chroma.process_magnitude_spectrum(&self.magnitude_spectrum, divisor_ratio, self.tuning);
+            self.chromagram.copy_from_slice(&nnls_chroma.treble_chroma);
+            self.bass_chromagram.copy_from_slice(&nnls_chroma.bass_chroma);
+        } else {
+            self.calculate_max_bin_chromagram(divisor_ratio);
+            self.calculate_bass_chromagram();
+        }
+
+        self.props.normalization.apply(&mut self.chromagram);
+        self.props.normalization.apply(&mut self.bass_chromagram);
+    }
+
+    fn calculate_max_bin_chromagram(&mut self, divisor_ratio: f64) {
         for n in 0..NUM_NOTES_IN_OCTAVE {
             let mut chroma_sum = 0.0;
-            for octave in 1..(NUM_OCTAVES + 1) {
+            for octave in 1..(self.props.num_octaves + 1) {
                 let mut note_sum = 0.0;
-                for harmonic in 1..(NUM_HARMONICS + 1) {
-                    let center_bin: isize = (NOTE_FREQUENCIES[n] * octave as f64 * harmonic as f64 / divisor_ratio).round() as isize;
-                    let min_bin = center_bin - (NUM_BINS_TO_SEARCH * harmonic as isize);
-                    let max_bin = center_bin + (NUM_BINS_TO_SEARCH * harmonic as isize);
-
-                    let mut max_val = 0.0;
+                for harmonic in 1..(self.props.num_harmonics + 1) {
+                    let center_bin: isize = (self.note_frequencies[n] * octave as f64 * harmonic as f64 / divisor_ratio).round() as isize;
+                    // the validated props keep the bins inside the spectrum, unless a tuning beyond +-50 cents is set
+                    let min_bin = (center_bin - (self.props.num_bins_to_search * harmonic) as isize).max(0) as usize;
+                    let max_bin = (center_bin + (self.props.num_bins_to_search * harmonic) as isize)
+                        .clamp(0, self.magnitude_spectrum.len() as isize) as usize;
 
-                    for k in min_bin..max_bin {
-                        if self.magnitude_spectrum[k as usize] > max_val {
-                            max_val = self.magnitude_spectrum[k as usize];
-                        }
-                    }
+                    let max_val = self.magnitude_spectrum[min_bin.min(max_bin)..max_bin].iter().cloned().fold(0.0, f64::max);
 
-                    note_sum += max_val / harmonic as f64;
+                    // square root compresses the magnitudes
+                    note_sum += max_val.sqrt() / harmonic as f64;
                 }
 
                 chroma_sum += note_sum;
             }
             self.chromagram[n] = chroma_sum;
         }
+    }
 
-        self.chroma_ready = true;
+    fn calculate_bass_chromagram(&mut self) {
+        let tuning_ratio = self.tuning.ratio();
+        let props = &self.props;
+        let magnitude_spectrum = &self.magnitude_spectrum;
+        for (pitch_class, bass_chroma) in self.bass_chromagram.iter_mut().enumerate() {
+            let mut chroma_sum = 0.0;
+            for octave in 1..(props.num_bass_octaves + 1) {
+                let (min_bin, max_bin) = props.bass_bin_range(pitch_class, octave, tuning_ratio);
+                let max_bin = max_bin.min(magnitude_spectrum.len() as isize - 1);
+                let max_val = (min_bin.max(0)..(max_bin + 1))
+                    .map(|k| magnitude_spectrum[k as usize])
+                    .fold(0.0, f64::max);
+                chroma_sum += max_val.sqrt();
+            }
+            *bass_chroma = chroma_sum;
+        }
     }
 
     fn calculate_magnitude_spectrum(&mut self) {
-        for i in 0..BUFFER_SIZE {
-            self.fft_in[i] = Complex::new(self.buffer[i] * HAMMING_WINDOW[i], 0.0);
+        let spectrum = self.spectrum_analyzer.magnitude_spectrum(self.buffer.iter().cloned());
+        self.magnitude_spectrum.copy_from_slice(spectrum);
+    }
+
+    fn down_sample_frame<S: Sample>(&mut self, input_audio_frame: &[S]) {
+        self.decimator.process(input_audio_frame, &mut self.downsampled_input_audio_frame);
+    }
+}
+
+pub struct ChromagramFrames<'a, I> {
+    chromagram: &'a mut Chromagram,
+    samples: I,
+    frame: Vec<f64>,
+    position: usize,
+    hop_start: usize,
+}
+
+impl<'a, S: Sample, I: Iterator<Item = S>> Iterator for ChromagramFrames<'a, I> {
+    type Item = ChromaFrame;
+
+    fn next(&mut self) -> Option<ChromaFrame> {
+        let frame_size = self.chromagram.props.frame_size;
+        loop {
+            self.frame.clear();
+            self.frame.extend(self.samples.by_ref().take(frame_size).map(Sample::to_f64));
+            if self.frame.is_empty() {
+                return None;
+            }
+            self.position += self.frame.len();
+            self.frame.resize(frame_size, 0.0);
+
+            self.chromagram.process_audio_frame(&self.frame).expect("frame is padded to frame_size");
+            if self.chromagram.is_ready() {
+                let props = &self.chromagram.props;
+                let center = self.position as f64 - (props.buffer_size * props.down_sampling_factor) as f64 / 2.0;
+                let frame = ChromaFrame::new(self.hop_start, center, props.sample_rate as f64, &self.chromagram.chromagram,
+                                             &self.chromagram.bass_chromagram, self.chromagram.is_silent());
+                self.hop_start = self.position;
+                return Some(frame);
+            }
         }
+    }
+}
 
-        // TODO: init in constructor
-        let mut planner = FFTplanner::new(false);
-        let fft = planner.plan_fft(BUFFER_SIZE);
-        fft.process(&mut self.fft_in, &mut self.fft_out);
+fn calculate_note_frequencies(reference_frequency: f64, tuning: Tuning, note_frequencies: &mut [f64]) {
+    for (i, note_frequency) in note_frequencies.iter_mut().enumerate() {
+        *note_frequency = reference_frequency * tuning.ratio() * f64::powf(2.0, (i as f64) / NUM_NOTES_IN_OCTAVE as f64);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use super::*;
+
+    #[test]
+    fn build_validates_props() {
+        let props = ChromagramInitProps::new(512, 48_000)
+            .with_reference_frequency(32.703)
+            .with_num_octaves(3)
+            .build()
+            .unwrap();
+        assert_eq!(props.num_octaves, 3);
 
-        for i in 0..self.magnitude_spectrum.len() {
-            self.magnitude_spectrum[i] = self.fft_out[i].norm_sqr().sqrt().sqrt();
+        let odd_frame_size = ChromagramInitProps::new(500, 48_000).with_down_sampling_factor(8).build();
+        assert!(odd_frame_size.is_err());
+        assert!(ChromagramInitProps::default().with_num_harmonics(0).build().is_err());
+    }
+
+    #[test]
+    fn rejects_bins_outside_of_spectrum() {
+        // the second harmonic of B in the second octave is above the Nyquist frequency of 1000 Hz
+        let low_sample_rate = Chromagram::new(ChromagramInitProps::new(256, 8000));
+        assert!(low_sample_rate.is_err());
+        assert!(Chromagram::new(ChromagramInitProps::new(256, 8000).with_num_harmonics(1)).is_ok());
+
+        let low_reference = ChromagramInitProps::default().with_reference_frequency(10.0).with_buffer_size(512);
+        assert!(Chromagram::new(low_reference).is_err());
+    }
+
+    #[test]
+    fn rejects_wrong_frame_length() {
+        let mut chromagram = Chromagram::new(ChromagramInitProps::default()).unwrap();
+        assert_eq!(chromagram.process_audio_frame(&[0.0; 100]),
+                   Err(ChromagramError::InvalidFrameLength { expected: 256, actual: 100 }));
+        assert_eq!(chromagram.process_audio_frame(&[0.0; 256]), Ok(()));
+    }
+
+    #[test]
+    fn chromagrams_with_different_props_coexist() {
+        let guitar = Chromagram::new(ChromagramInitProps::default()).unwrap();
+        let bass = Chromagram::new(ChromagramInitProps::default()
+            .with_reference_frequency(32.703)
+            .with_buffer_size(4096)).unwrap();
+        assert!((guitar.note_frequencies[0] - 4.0 * bass.note_frequencies[0]).abs() < 0.01);
+        assert_eq!(bass.spectrum_analyzer.size(), 4096);
+        assert_eq!(guitar.spectrum_analyzer.size(), 8192);
+    }
+
+    #[test]
+    fn chroma_of_triad() {
+        let props = ChromagramInitProps::default();
+        let sample_rate = props.sample_rate as f64;
+        let frame_size = props.frame_size;
+        let mut chromagram = Chromagram::new(props).unwrap();
+
+        // C4, E4, G4
+        let frequencies = [261.63, 329.63, 392.0];
+        let signal: Vec<f64> = (0..frame_size * 64)
+            .map(|i| frequencies.iter().map(|f| (2.0 * PI * f * i as f64 / sample_rate).sin()).sum())
+            .collect();
+        for frame in signal.chunks(frame_size) {
+            chromagram.process_audio_frame(frame).unwrap();
+        }
+
+        let mut strongest: Vec<usize> = (0..NUM_NOTES_IN_OCTAVE).collect();
+        strongest.sort_by(|&a, &b| chromagram.chromagram[b].partial_cmp(&chromagram.chromagram[a]).unwrap());
+        strongest.truncate(3);
+        strongest.sort();
+        assert_eq!(strongest, vec![0, 4, 7]);
+    }
+
+    #[test]
+    fn bass_chroma_of_inversion() {
+        let props = ChromagramInitProps::default();
+        let sample_rate = props.sample_rate as f64;
+        let frame_size = props.frame_size;
+        let mut chromagram = Chromagram::new(props).unwrap();
+
+        // C major with E2 in the bass
+        let frequencies = [82.41, 261.63, 329.63, 392.0];
+        let signal: Vec<f64> = (0..frame_size * 64)
+            .map(|i| frequencies.iter().map(|f| (2.0 * PI * f * i as f64 / sample_rate).sin()).sum())
+            .collect();
+        for frame in signal.chunks(frame_size) {
+            chromagram.process_audio_frame(frame).unwrap();
         }
+
+        let bass = &chromagram.bass_chromagram;
+        let strongest_bass = (0..NUM_NOTES_IN_OCTAVE).max_by(|&a, &b| bass[a].partial_cmp(&bass[b]).unwrap());
+        assert_eq!(strongest_bass, Some(4), "bass chroma = {:?}", bass);
+        assert!(bass[0] < 0.5 * bass[4], "bass chroma = {:?}", bass);
     }
 
-    fn down_sample_frame(&mut self, input_audio_frame: &[f64]) {
-        let b0 = 0.2929;
-        let b1 = 0.5858;
-        let b2 = 0.2929;
-        let a1 = -0.0000;
-        let a2 = 0.1716;
-        let mut x_1 = 0.0;
-        let mut x_2 = 0.0;
-        let mut y_1 = 0.0;
-        let mut y_2 = 0.0;
+    #[test]
+    fn silence_gate() {
+        let props = ChromagramInitProps::default().with_silence_threshold(0.01).with_normalization(Normalization::L2);
+        let sample_rate = props.sample_rate as f64;
+        let mut chromagram = Chromagram::new(props).unwrap();
+
+        let silence = chromagram.frames(vec![0.0; 8192]).last().unwrap();
+        assert!(silence.silent);
+        assert!(silence.chroma.iter().all(|&x| x == 0.0));
 
-        for i in 0..self.props.frame_size {
-            self.filtered_frame[i] = self.filtered_frame[i] * b0 + x_1 * b1 + x_2 * b2 - y_1 * a1 - y_2 * a2;
+        let signal = (0..32_768).map(|i| (2.0 * PI * 440.0 * i as f64 / sample_rate).sin());
+        let frame = chromagram.frames(signal).last().unwrap();
+        assert!(!frame.silent);
+        assert!((frame.chroma.iter().map(|x| x * x).sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn frames_over_signal() {
+        let props = ChromagramInitProps::default();
+        let sample_rate = props.sample_rate as f64;
+        let interval = props.chroma_calculation_interval;
+        let mut chromagram = Chromagram::new(props).unwrap();
+
+        let signal = (0..interval * 5 + 100).map(|i| (2.0 * PI * 440.0 * i as f64 / sample_rate).sin());
+        let frames: Vec<ChromaFrame> = chromagram.frames(signal).collect();
+
+        let start_samples: Vec<usize> = frames.iter().map(|frame| frame.start_sample).collect();
+        assert_eq!(start_samples, vec![0, interval, 2 * interval, 3 * interval, 4 * interval]);
+        assert_eq!(frames[1].time_secs, interval as f64 / sample_rate);
+        // the window of 8192 samples at a quarter of the sample rate ends after the second hop
+        assert_eq!(frames[1].center_secs, (2 * interval) as f64 / sample_rate - 16_384.0 / sample_rate);
+        assert_eq!(frames[1].bin_centers_secs, vec![frames[1].center_secs; 12]);
+        assert_eq!(frames[4].chroma, chromagram.chromagram);
+        // the last padded frame didn't complete a hop
+        assert!(!chromagram.is_ready());
+    }
 
-            x_2 = x_1;
-            x_1 = input_audio_frame[i];
-            y_2 = y_1;
-            y_1 = self.filtered_frame[i];
+    #[test]
+    fn hpcp_chroma_method() {
+        let hpcp_props = HpcpInitProps { size: 24, ..Default::default() };
+        let props = ChromagramInitProps::default().with_chroma_method(ChromaMethod::Hpcp(hpcp_props));
+        let sample_rate = props.sample_rate as f64;
+        let frame_size = props.frame_size;
+        let mut chromagram = Chromagram::new(props).unwrap();
+
+        // A3 a quarter tone sharp
+        let frequency = 220.0 * f64::powf(2.0, 0.5 / 12.0);
+        let signal: Vec<f64> = (0..frame_size * 64)
+            .map(|i| (2.0 * PI * frequency * i as f64 / sample_rate).sin())
+            .collect();
+        for frame in signal.chunks(frame_size) {
+            chromagram.process_audio_frame(frame).unwrap();
         }
 
-        for i in 0..self.downsampled_input_audio_frame.len() {
-            self.downsampled_input_audio_frame[i] = self.filtered_frame[i * DOWN_SAMPLING_FACTOR];
+        assert_eq!(chromagram.chromagram.len(), 24);
+        let strongest = (0..24).max_by(|&a, &b| chromagram.chromagram[a].partial_cmp(&chromagram.chromagram[b]).unwrap());
+        assert_eq!(strongest, Some(9 * 2 + 1));
+    }
+
+    #[test]
+    fn nnls_chroma_method() {
+        let props = ChromagramInitProps::default().with_chroma_method(ChromaMethod::Nnls(NnlsChromaInitProps::default()));
+        let sample_rate = props.sample_rate as f64;
+        let frame_size = props.frame_size;
+        let mut chromagram = Chromagram::new(props).unwrap();
+
+        // C major with E in the bass: E2, C4, E4, G4 with decaying harmonics
+        let signal: Vec<f64> = (0..frame_size * 160)
+            .map(|i| [40.0, 60.0, 64.0, 67.0].iter()
+                .flat_map(|&note| (1..6).map(move |h| (note, h)))
+                .map(|(note, h)| {
+                    let frequency = Tuning::default().note_frequency(note) * h as f64;
+                    0.1 * 0.8f64.powi(h - 1) * (2.0 * PI * frequency * i as f64 / sample_rate).sin()
+                })
+                .sum())
+            .collect();
+        for frame in signal.chunks(frame_size) {
+            chromagram.process_audio_frame(frame).unwrap();
         }
+
+        let strongest = |chroma: &[f64]| (0..12).max_by(|&a, &b| chroma[a].total_cmp(&chroma[b])).unwrap();
+        assert_eq!(strongest(&chromagram.bass_chromagram), 4);
+        let treble = &chromagram.chromagram;
+        let mut strongest_treble: Vec<usize> = (0..12).collect();
+        strongest_treble.sort_by(|&a, &b| treble[b].total_cmp(&treble[a]));
+        strongest_treble.truncate(3);
+        strongest_treble.sort();
+        assert_eq!(strongest_treble, vec![0, 4, 7], "chroma = {:?}", treble);
+
+        let unreachable_notes = NnlsChromaInitProps { treble_range: (48, 120), ..Default::default() };
+        assert!(Chromagram::new(ChromagramInitProps::default().with_chroma_method(ChromaMethod::Nnls(unreachable_notes))).is_err());
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,835 @@
+use std::f64;
+use std::f64::consts::PI;
+
+use rusty_machine::learning::{LearningResult, SupModel, UnSupModel};
+use rusty_machine::learning::error::{Error, ErrorKind};
+use rusty_machine::learning::gmm::{CovOption, GaussianMixtureModel};
+use rusty_machine::learning::logistic_reg::LogisticRegressor;
+use rusty_machine::learning::naive_bayes::{Gaussian, NaiveBayes};
+use rusty_machine::learning::optim::grad_desc::GradientDesc;
+use rusty_machine::linalg::{Axes, BaseMatrix, BaseMatrixMut, Matrix, Vector};
+
+/// A model, which classifies feature vectors (e.g. gromagrams) into labels, used by the `ChordDetector`.
+pub trait Classifier {
+    /// Trains the model from scratch. Every row of the inputs is a feature vector,
+    /// labels contains the index of the label of every row.
+    fn train(&mut self, inputs: &Matrix<f64>, labels: &[usize], num_labels: usize) -> LearningResult<()>;
+
+    /// Size of the feature vectors of the trained model, None if the model isn't trained.
+    fn num_features(&self) -> Option<usize>;
+
+    /// Probability of every label, which sum up to 1, or None if the model isn't trained
+    /// or the features have another size than the training features.
+    fn probabilities(&self, features: &[f64]) -> Option<Vec<f64>> {
+        if self.num_features() != Some(features.len()) {
+            return None;
+        }
+        self.trained_probabilities(features)
+    }
+
+    /// `probabilities` of features, which have the size of the training features.
+    fn trained_probabilities(&self, features: &[f64]) -> Option<Vec<f64>>;
+
+    /// Name of the model, which is saved together with its parameters.
+    fn kind(&self) -> &'static str;
+
+    /// The trained model as named lists of numbers, empty if the model isn't trained.
+    fn parameters(&self) -> Vec<(String, Vec<f64>)>;
+
+    /// Restores a model, which was trained with feature vectors of size num_features, from its `parameters`.
+    /// Empty parameters give an untrained model, invalid ones an error.
+    fn set_parameters(&mut self, parameters: &[(String, Vec<f64>)], num_features: usize, num_labels: usize) -> LearningResult<()>;
+}
+
+/// Gaussian naive Bayes, the posterior probabilities are calculated from the joint log likelihoods.
+/// The model is trained by rusty-machine, which can't restore a model from its parameters,
+/// so the means and variances are kept here.
+#[derive(Debug, Default)]
+pub struct NaiveBayesClassifier {
+    // a single component per label
+    models: Vec<LabelMixture>,
+}
+
+impl Classifier for NaiveBayesClassifier {
+    fn train(&mut self, inputs: &Matrix<f64>, labels: &[usize], num_labels: usize) -> LearningResult<()> {
+        self.models.clear();
+        let mut model = NaiveBayes::<Gaussian>::new();
+        model.train(inputs, &one_hot(labels, num_labels, 0.0))?;
+        let priors = model.class_prior().ok_or_else(Error::new_untrained)?;
+        let distr = model.distr().ok_or_else(Error::new_untrained)?;
+        self.models = priors.iter().enumerate().map(|(label_i, prior)| LabelMixture {
+            log_prior: prior.ln(),
+            weights: vec![1.0],
+            means: distr.theta().select_rows(&[label_i]),
+            variances: distr.sigma().select_rows(&[label_i]),
+        }).collect();
+        Ok(())
+    }
+
+    fn num_features(&self) -> Option<usize> {
+        self.models.first().map(|mixture| mixture.means.cols())
+    }
+
+    fn trained_probabilities(&self, features: &[f64]) -> Option<Vec<f64>> {
+        mixture_probabilities(&self.models, features)
+    }
+
+    fn kind(&self) -> &'static str {
+        "naive-bayes"
+    }
+
+    fn parameters(&self) -> Vec<(String, Vec<f64>)> {
+        mixture_parameters(&self.models)
+    }
+
+    fn set_parameters(&mut self, parameters: &[(String, Vec<f64>)], num_features: usize, num_labels: usize) -> LearningResult<()> {
+        self.models.clear();
+        self.models = mixtures_from_parameters(parameters, num_features, num_labels)?;
+        Ok(())
+    }
+}
+
+/// One logistic regression per label (one vs. rest), whose outputs are normalized to a sum of 1.
+/// The regressions are trained by rusty-machine and evaluated here with their parameters.
+#[derive(Debug, Default)]
+pub struct LogisticRegressionClassifier {
+    pub gradient_descent: GradientDesc,
+    // one row per label, the first column is the bias
+    weights: Vec<Vec<f64>>,
+}
+
+impl Classifier for LogisticRegressionClassifier {
+    fn train(&mut self, inputs: &Matrix<f64>, labels: &[usize], num_labels: usize) -> LearningResult<()> {
+        self.weights.clear();
+        let mut weights = Vec::with_capacity(num_labels);
+        for label_i in 0..num_labels {
+            let mut model = LogisticRegressor::new(self.gradient_descent);
+            model.train(inputs, &one_vs_rest(labels, label_i, 0.0))?;
+            weights.push(model.parameters().ok_or_else(Error::new_untrained)?.data().clone());
+        }
+        self.weights = weights;
+        Ok(())
+    }
+
+    fn num_features(&self) -> Option<usize> {
+        self.weights.first().map(|weights| weights.len() - 1)
+    }
+
+    fn trained_probabilities(&self, features: &[f64]) -> Option<Vec<f64>> {
+        normalize(self.weights.iter().map(|weights| sigmoid(decision_value(weights, features))).collect())
+    }
+
+    fn kind(&self) -> &'static str {
+        "logistic-regression"
+    }
+
+    fn parameters(&self) -> Vec<(String, Vec<f64>)> {
+        rows_parameters("weights", &self.weights)
+    }
+
+    fn set_parameters(&mut self, parameters: &[(String, Vec<f64>)], num_features: usize, num_labels: usize) -> LearningResult<()> {
+        self.weights.clear();
+        self.weights = rows_from_parameters(parameters, "weights", num_labels, num_features + 1)?;
+        Ok(())
+    }
+}
+
+/// One linear support vector machine per label (one vs. rest), trained by the Pegasos algorithm
+/// with a fixed order of the examples, so training is reproducible.
+///
+/// The SVM of rusty-machine 0.5.4 ignores the support vector weights while training and always
+/// predicts the larger class in one vs. rest mode, so the SVMs are trained here.
+/// The probabilities are the softmax of the distances to the hyperplanes.
+#[derive(Debug)]
+pub struct SvmClassifier {
+    /// Regularization, a larger value gives a wider margin
+    pub lambda: f64,
+    /// Number of passes over the training data
+    pub epochs: usize,
+    // one row per label, the first column is the bias
+    weights: Vec<Vec<f64>>,
+}
+
+impl SvmClassifier {
+    pub fn new(lambda: f64, epochs: usize) -> Self {
+        assert!(lambda > 0.0, "lambda must be greater than 0");
+        Self { lambda, epochs, weights: Vec::new() }
+    }
+}
+
+impl Default for SvmClassifier {
+    fn default() -> Self {
+        Self::new(0.001, 50)
+    }
+}
+
+impl Classifier for SvmClassifier {
+    fn train(&mut self, inputs: &Matrix<f64>, labels: &[usize], num_labels: usize) -> LearningResult<()> {
+        self.weights = vec![vec![0.0; inputs.cols() + 1]; num_labels];
+        for (label_i, weights) in self.weights.iter_mut().enumerate() {
+            let mut t = 0;
+            for _ in 0..self.epochs {
+                for (input, &l) in inputs.iter_rows().zip(labels) {
+                    t += 1;
+                    let target = if l == label_i { 1.0 } else { -1.0 };
+                    let learning_rate = 1.0 / (self.lambda * t as f64);
+                    let violates_margin = target * decision_value(weights, input) < 1.0;
+                    // the bias isn't regularized
+                    for w in &mut weights[1..] {
+                        *w *= 1.0 - learning_rate * self.lambda;
+                    }
+                    if violates_margin {
+                        weights[0] += learning_rate * target;
+                        for (w, x) in weights[1..].iter_mut().zip(input) {
+                            *w += learning_rate * target * x;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn num_features(&self) -> Option<usize> {
+        self.weights.first().map(|weights| weights.len() - 1)
+    }
+
+    fn trained_probabilities(&self, features: &[f64]) -> Option<Vec<f64>> {
+        let decision_values: Vec<f64> = self.weights.iter().map(|weights| decision_value(weights, features)).collect();
+        softmax(&decision_values)
+    }
+
+    fn kind(&self) -> &'static str {
+        "svm"
+    }
+
+    fn parameters(&self) -> Vec<(String, Vec<f64>)> {
+        rows_parameters("weights", &self.weights)
+    }
+
+    fn set_parameters(&mut self, parameters: &[(String, Vec<f64>)], num_features: usize, num_labels: usize) -> LearningResult<()> {
+        self.weights.clear();
+        self.weights = rows_from_parameters(parameters, "weights", num_labels, num_features + 1)?;
+        Ok(())
+    }
+}
+
+fn decision_value(weights: &[f64], features: &[f64]) -> f64 {
+    weights[0] + weights[1..].iter().zip(features).map(|(w, x)| w * x).sum::<f64>()
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// A small feed forward neural network with sigmoid hidden layers and a softmax output,
+/// trained by batch gradient descent on the cross entropy.
+///
+/// The back propagation of the rusty-machine 0.5.4 network calculates transposed gradients
+/// for non square layers, so the network is trained here. The inputs are standardized per feature.
+#[derive(Debug)]
+pub struct NeuralNetClassifier {
+    pub hidden_layer_sizes: Vec<usize>,
+    pub learning_rate: f64,
+    pub iterations: usize,
+    // one matrix per layer with a row per input and a column per output, the first row contains the biases
+    weights: Vec<Matrix<f64>>,
+    means: Vec<f64>,
+    deviations: Vec<f64>,
+}
+
+impl Default for NeuralNetClassifier {
+    fn default() -> Self {
+        Self {
+            hidden_layer_sizes: vec![24],
+            learning_rate: 0.5,
+            iterations: 500,
+            weights: Vec::new(),
+            means: Vec::new(),
+            deviations: Vec::new(),
+        }
+    }
+}
+
+impl NeuralNetClassifier {
+    fn standardize(&self, inputs: &Matrix<f64>) -> Matrix<f64> {
+        let standardized: Vec<f64> = inputs.iter_rows()
+            .flat_map(|input| input.iter().zip(&self.means).zip(&self.deviations).map(|((x, mean), deviation)| (x - mean) / deviation))
+            .collect();
+        Matrix::new(inputs.rows(), inputs.cols(), standardized)
+    }
+
+    /// The activations of all layers, beginning with the inputs. All but the output have a bias column of 1.
+    fn forward(&self, inputs: Matrix<f64>) -> Vec<Matrix<f64>> {
+        let mut activations = vec![with_bias(inputs)];
+        for (layer, weights) in self.weights.iter().enumerate() {
+            let z = &activations[layer] * weights;
+            if layer + 1 < self.weights.len() {
+                activations.push(with_bias(z.apply(&sigmoid)));
+            } else {
+                let probabilities: Vec<f64> = z.iter_rows().flat_map(|row| softmax(row).unwrap_or_else(|| vec![0.0; row.len()])).collect();
+                activations.push(Matrix::new(z.rows(), z.cols(), probabilities));
+            }
+        }
+        activations
+    }
+}
+
+impl Classifier for NeuralNetClassifier {
+    fn train(&mut self, inputs: &Matrix<f64>, labels: &[usize], num_labels: usize) -> LearningResult<()> {
+        self.means = inputs.mean(Axes::Row).into_vec();
+        self.deviations = inputs.variance(Axes::Row)?.into_vec().into_iter().map(|v| v.max(MIN_VARIANCE).sqrt()).collect();
+        let inputs = self.standardize(inputs);
+        let targets = one_hot(labels, num_labels, 0.0);
+
+        let mut layer_sizes = vec![inputs.cols()];
+        layer_sizes.extend(&self.hidden_layer_sizes);
+        layer_sizes.push(num_labels);
+        // small deterministic initial weights, which differ between the neurons
+        self.weights = layer_sizes.windows(2).map(|sizes| {
+            let scale = 1.0 / (sizes[0] as f64).sqrt();
+            let data = (0..(sizes[0] + 1) * sizes[1]).map(|i| scale * (i as f64 * 12.9898).sin()).collect::<Vec<_>>();
+            Matrix::new(sizes[0] + 1, sizes[1], data)
+        }).collect();
+
+        let num_examples = inputs.rows() as f64;
+        for _ in 0..self.iterations {
+            let activations = self.forward(inputs.clone());
+            // gradient of the cross entropy of the softmax outputs
+            let mut delta = &activations[activations.len() - 1] - &targets;
+            for layer in (0..self.weights.len()).rev() {
+                let gradient = activations[layer].transpose() * &delta / num_examples;
+                if layer > 0 {
+                    let a = &activations[layer];
+                    let back = (&delta * self.weights[layer].transpose()).elemul(&a.clone().apply(&|x| x * (1.0 - x)));
+                    // drop the bias column
+                    delta = back.select_cols(&(1..back.cols()).collect::<Vec<_>>());
+                }
+                self.weights[layer] -= gradient * self.learning_rate;
+            }
+        }
+        Ok(())
+    }
+
+    fn num_features(&self) -> Option<usize> {
+        self.weights.first().map(|weights| weights.rows() - 1)
+    }
+
+    fn trained_probabilities(&self, features: &[f64]) -> Option<Vec<f64>> {
+        let activations = self.forward(self.standardize(&row(features)));
+        Some(activations[activations.len() - 1].data().clone())
+    }
+
+    fn kind(&self) -> &'static str {
+        "neural-net"
+    }
+
+    fn parameters(&self) -> Vec<(String, Vec<f64>)> {
+        if self.weights.is_empty() {
+            return Vec::new();
+        }
+        // the sizes of all layers, including the inputs and the outputs
+        let mut layer_sizes = vec![self.weights[0].rows() - 1];
+        layer_sizes.extend(self.weights.iter().map(|weights| weights.cols()));
+        vec![
+            ("layer_sizes".to_string(), layer_sizes.into_iter().map(|size| size as f64).collect()),
+            ("means".to_string(), self.means.clone()),
+            ("deviations".to_string(), self.deviations.clone()),
+            ("weights".to_string(), self.weights.iter().flat_map(|weights| weights.data().clone()).collect()),
+        ]
+    }
+
+    fn set_parameters(&mut self, parameters: &[(String, Vec<f64>)], num_features: usize, num_labels: usize) -> LearningResult<()> {
+        self.weights.clear();
+        if parameters.is_empty() {
+            return Ok(());
+        }
+        let layer_sizes = parameter(parameters, "layer_sizes")?.iter().map(|&size| count(size)).collect::<LearningResult<Vec<_>>>()?;
+        if layer_sizes.len() < 2 || layer_sizes[0] != num_features || layer_sizes[layer_sizes.len() - 1] != num_labels {
+            return Err(invalid_parameters(format!("layer sizes {:?} don't fit {} features and {} labels", layer_sizes, num_features, num_labels)));
+        }
+        let num_weights = layer_sizes.windows(2)
+            .try_fold(0usize, |sum, sizes| sizes[0].checked_add(1)?.checked_mul(sizes[1])?.checked_add(sum))
+            .ok_or_else(|| invalid_parameters(format!("too large layer sizes {:?}", layer_sizes)))?;
+        let means = sized_parameter(parameters, "means", num_features)?;
+        let deviations = sized_parameter(parameters, "deviations", num_features)?;
+        let mut weights = sized_parameter(parameters, "weights", num_weights)?;
+
+        self.means = means.to_vec();
+        self.deviations = deviations.to_vec();
+        self.weights = layer_sizes.windows(2).map(|sizes| {
+            let (layer, rest) = weights.split_at((sizes[0] + 1) * sizes[1]);
+            weights = rest;
+            Matrix::new(sizes[0] + 1, sizes[1], layer)
+        }).collect();
+        Ok(())
+    }
+}
+
+fn with_bias(m: Matrix<f64>) -> Matrix<f64> {
+    Matrix::ones(m.rows(), 1).hcat(&m)
+}
+
+/// A Gaussian mixture model with diagonal covariances per label.
+/// The posterior probabilities use the share of every label in the training data as prior.
+#[derive(Debug)]
+pub struct GaussianMixtureClassifier {
+    pub num_components: usize,
+    models: Vec<LabelMixture>,
+}
+
+/// The Gaussian mixture of a label, with one row per component.
+#[derive(Debug)]
+struct LabelMixture {
+    log_prior: f64,
+    weights: Vec<f64>,
+    means: Matrix<f64>,
+    variances: Matrix<f64>,
+}
+
+impl LabelMixture {
+    /// Joint log likelihood of the label and the features.
+    fn log_likelihood(&self, features: &[f64]) -> Option<f64> {
+        let component_log_likelihoods: Vec<f64> = self.weights.iter().enumerate().map(|(k, weight)| {
+            let log_density: f64 = features.iter().zip(self.means.get_row(k)?).zip(self.variances.get_row(k)?)
+                .map(|((x, mean), variance)| -0.5 * ((x - mean) * (x - mean) / variance + (2.0 * PI * variance).ln()))
+                .sum();
+            Some(weight.ln() + log_density)
+        }).collect::<Option<_>>()?;
+        Some(self.log_prior + log_sum_exp(&component_log_likelihoods))
+    }
+}
+
+fn mixture_probabilities(models: &[LabelMixture], features: &[f64]) -> Option<Vec<f64>> {
+    let log_likelihoods: Vec<f64> = models.iter().map(|mixture| mixture.log_likelihood(features)).collect::<Option<_>>()?;
+    softmax(&log_likelihoods)
+}
+
+fn mixture_parameters(models: &[LabelMixture]) -> Vec<(String, Vec<f64>)> {
+    if models.is_empty() {
+        return Vec::new();
+    }
+    vec![
+        ("log_priors".to_string(), models.iter().map(|mixture| mixture.log_prior).collect()),
+        ("weights".to_string(), models.iter().flat_map(|mixture| mixture.weights.clone()).collect()),
+        ("means".to_string(), models.iter().flat_map(|mixture| mixture.means.data().clone()).collect()),
+        ("variances".to_string(), models.iter().flat_map(|mixture| mixture.variances.data().clone()).collect()),
+    ]
+}
+
+/// The mixtures saved by `mixture_parameters`, every label has the same number of components.
+fn mixtures_from_parameters(parameters: &[(String, Vec<f64>)], num_features: usize, num_labels: usize) -> LearningResult<Vec<LabelMixture>> {
+    if parameters.is_empty() {
+        return Ok(Vec::new());
+    }
+    let log_priors = sized_parameter(parameters, "log_priors", num_labels)?;
+    let weights = parameter(parameters, "weights")?;
+    if num_labels == 0 || weights.is_empty() || weights.len() % num_labels != 0 {
+        return Err(invalid_parameters(format!("{} mixture weights don't fit {} labels", weights.len(), num_labels)));
+    }
+    let num_components = weights.len() / num_labels;
+    let size = num_components * num_features;
+    let means = sized_parameter(parameters, "means", num_labels * size)?;
+    let variances = sized_parameter(parameters, "variances", num_labels * size)?;
+    Ok((0..num_labels).map(|label_i| LabelMixture {
+        log_prior: log_priors[label_i],
+        weights: weights[label_i * num_components..(label_i + 1) * num_components].to_vec(),
+        means: Matrix::new(num_components, num_features, &means[label_i * size..(label_i + 1) * size]),
+        variances: Matrix::new(num_components, num_features, &variances[label_i * size..(label_i + 1) * size]),
+    }).collect())
+}
+
+// Lower limit of the variances, features with a constant value in the training data would have a variance of 0.
+const MIN_VARIANCE: f64 = 1e-6;
+
+impl GaussianMixtureClassifier {
+    pub fn new(num_components: usize) -> Self {
+        Self { num_components, models: Vec::new() }
+    }
+}
+
+impl Default for GaussianMixtureClassifier {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl Classifier for GaussianMixtureClassifier {
+    fn train(&mut self, inputs: &Matrix<f64>, labels: &[usize], num_labels: usize) -> LearningResult<()> {
+        self.models.clear();
+        let mut models = Vec::with_capacity(num_labels);
+        for label_i in 0..num_labels {
+            let rows: Vec<usize> = (0..labels.len()).filter(|&i| labels[i] == label_i).collect();
+            if rows.len() < self.num_components {
+                return Err(Error::new(ErrorKind::InvalidData, "Every label needs at least one example per mixture component"));
+            }
+            let mut model = GaussianMixtureModel::new(self.num_components);
+            model.cov_option = CovOption::Diagonal;
+            model.train(&inputs.select_rows(&rows))?;
+
+            let means = model.means().ok_or_else(Error::new_untrained)?.clone();
+            let covariances = model.covariances().ok_or_else(Error::new_untrained)?;
+            let variances: Vec<f64> = covariances.iter()
+                .flat_map(|covariance| covariance.diag().into_vec())
+                .map(|variance| variance.max(MIN_VARIANCE))
+                .collect();
+            let prior = rows.len() as f64 / labels.len() as f64;
+            models.push(LabelMixture {
+                log_prior: prior.ln(),
+                weights: model.mixture_weights().data().clone(),
+                means,
+                variances: Matrix::new(self.num_components, inputs.cols(), variances),
+            });
+        }
+        self.models = models;
+        Ok(())
+    }
+
+    fn num_features(&self) -> Option<usize> {
+        self.models.first().map(|mixture| mixture.means.cols())
+    }
+
+    fn trained_probabilities(&self, features: &[f64]) -> Option<Vec<f64>> {
+        mixture_probabilities(&self.models, features)
+    }
+
+    fn kind(&self) -> &'static str {
+        "gaussian-mixture"
+    }
+
+    fn parameters(&self) -> Vec<(String, Vec<f64>)> {
+        mixture_parameters(&self.models)
+    }
+
+    fn set_parameters(&mut self, parameters: &[(String, Vec<f64>)], num_features: usize, num_labels: usize) -> LearningResult<()> {
+        self.models.clear();
+        self.models = mixtures_from_parameters(parameters, num_features, num_labels)?;
+        Ok(())
+    }
+}
+
+/// Compares the cosine similarity of a feature vector to the mean feature vector of every label.
+/// The similarities are normalized to a sum of 1.
+#[derive(Debug, Default)]
+pub struct NearestTemplateClassifier {
+    templates: Vec<Vec<f64>>,
+}
+
+impl Classifier for NearestTemplateClassifier {
+    fn train(&mut self, inputs: &Matrix<f64>, labels: &[usize], num_labels: usize) -> LearningResult<()> {
+        self.templates = vec![vec![0.0; inputs.cols()]; num_labels];
+        for (input, &label_i) in inputs.iter_rows().zip(labels) {
+            for (t, x) in self.templates[label_i].iter_mut().zip(input) {
+                *t += x;
+            }
+        }
+        Ok(())
+    }
+
+    fn num_features(&self) -> Option<usize> {
+        self.templates.first().map(Vec::len)
+    }
+
+    fn trained_probabilities(&self, features: &[f64]) -> Option<Vec<f64>> {
+        let similarities = self.templates.iter()
+            .map(|template| cosine_similarity(template, features).max(0.0))
+            .collect();
+        normalize(similarities)
+    }
+
+    fn kind(&self) -> &'static str {
+        "nearest-template"
+    }
+
+    fn parameters(&self) -> Vec<(String, Vec<f64>)> {
+        rows_parameters("templates", &self.templates)
+    }
+
+    fn set_parameters(&mut self, parameters: &[(String, Vec<f64>)], num_features: usize, num_labels: usize) -> LearningResult<()> {
+        self.templates.clear();
+        self.templates = rows_from_parameters(parameters, "templates", num_labels, num_features)?;
+        Ok(())
+    }
+}
+
+/// The k nearest training examples (by euclidean distance) vote for their labels.
+/// rusty-machine has no k-nearest neighbours model, so the search is done here.
+#[derive(Debug)]
+pub struct KNearestNeighboursClassifier {
+    pub k: usize,
+    inputs: Vec<Vec<f64>>,
+    labels: Vec<usize>,
+    num_labels: usize,
+}
+
+impl KNearestNeighboursClassifier {
+    pub fn new(k: usize) -> Self {
+        assert!(k > 0, "k must be greater than 0");
+        Self { k, inputs: Vec::new(), labels: Vec::new(), num_labels: 0 }
+    }
+}
+
+impl Default for KNearestNeighboursClassifier {
+    fn default() -> Self {
+        Self::new(5)
+    }
+}
+
+impl Classifier for KNearestNeighboursClassifier {
+    fn train(&mut self, inputs: &Matrix<f64>, labels: &[usize], num_labels: usize) -> LearningResult<()> {
+        self.inputs = inputs.iter_rows().map(|input| input.to_vec()).collect();
+        self.labels = labels.to_vec();
+        self.num_labels = num_labels;
+        Ok(())
+    }
+
+    fn num_features(&self) -> Option<usize> {
+        self.inputs.first().map(Vec::len)
+    }
+
+    fn trained_probabilities(&self, features: &[f64]) -> Option<Vec<f64>> {
+        let mut distances: Vec<(f64, usize)> = self.inputs.iter().zip(&self.labels)
+            .map(|(input, &label_i)| (input.iter().zip(features).map(|(a, b)| (a - b) * (a - b)).sum(), label_i))
+            .collect();
+        distances.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(::std::cmp::Ordering::Equal));
+        let mut votes = vec![0.0; self.num_labels];
+        for &(_, label_i) in distances.iter().take(self.k) {
+            votes[label_i] += 1.0;
+        }
+        normalize(votes)
+    }
+
+    fn kind(&self) -> &'static str {
+        "k-nearest-neighbours"
+    }
+
+    /// The training examples, the model has no other parameters.
+    fn parameters(&self) -> Vec<(String, Vec<f64>)> {
+        if self.inputs.is_empty() {
+            return Vec::new();
+        }
+        vec![
+            ("labels".to_string(), self.labels.iter().map(|&label_i| label_i as f64).collect()),
+            ("inputs".to_string(), self.inputs.concat()),
+        ]
+    }
+
+    fn set_parameters(&mut self, parameters: &[(String, Vec<f64>)], num_features: usize, num_labels: usize) -> LearningResult<()> {
+        self.inputs.clear();
+        if parameters.is_empty() {
+            return Ok(());
+        }
+        let labels = parameter(parameters, "labels")?.iter().map(|&label_i| count(label_i)).collect::<LearningResult<Vec<_>>>()?;
+        if let Some(label_i) = labels.iter().find(|&&label_i| label_i >= num_labels) {
+            return Err(invalid_parameters(format!("label index {} out of range", label_i)));
+        }
+        let inputs = rows_from_parameters(parameters, "inputs", labels.len(), num_features)?;
+        self.labels = labels;
+        self.num_labels = num_labels;
+        self.inputs = inputs;
+        Ok(())
+    }
+}
+
+fn row(features: &[f64]) -> Matrix<f64> {
+    Matrix::new(1, features.len(), features)
+}
+
+/// One row per label, with 1 at the index of the label and `other` everywhere else.
+fn one_hot(labels: &[usize], num_labels: usize, other: f64) -> Matrix<f64> {
+    let mut data = vec![other; labels.len() * num_labels];
+    for (i, &label_i) in labels.iter().enumerate() {
+        data[i * num_labels + label_i] = 1.0;
+    }
+    Matrix::new(labels.len(), num_labels, data)
+}
+
+fn one_vs_rest(labels: &[usize], label_i: usize, other: f64) -> Vector<f64> {
+    labels.iter().map(|&l| if l == label_i { 1.0 } else { other }).collect::<Vec<_>>().into()
+}
+
+fn invalid_parameters(reason: String) -> Error {
+    Error::new(ErrorKind::InvalidParameters, reason)
+}
+
+fn parameter<'a>(parameters: &'a [(String, Vec<f64>)], name: &str) -> LearningResult<&'a [f64]> {
+    parameters.iter().find(|parameter| parameter.0 == name).map(|parameter| &parameter.1[..])
+        .ok_or_else(|| invalid_parameters(format!("missing parameter {}", name)))
+}
+
+fn sized_parameter<'a>(parameters: &'a [(String, Vec<f64>)], name: &str, len: usize) -> LearningResult<&'a [f64]> {
+    let values = parameter(parameters, name)?;
+    if values.len() != len {
+        return Err(invalid_parameters(format!("expected {} values of {}, got {}", len, name, values.len())));
+    }
+    Ok(values)
+}
+
+/// A size or an index, which is stored as a parameter.
+fn count(value: f64) -> LearningResult<usize> {
+    if value >= 0.0 && value.fract() == 0.0 && value <= u32::MAX as f64 {
+        Ok(value as usize)
+    } else {
+        Err(invalid_parameters(format!("invalid count {}", value)))
+    }
+}
+
+fn rows_parameters(name: &str, rows: &[Vec<f64>]) -> Vec<(String, Vec<f64>)> {
+    if rows.is_empty() {
+        return Vec::new();
+    }
+    vec![(name.to_string(), rows.concat())]
+}
+
+fn rows_from_parameters(parameters: &[(String, Vec<f64>)], name: &str, num_rows: usize, row_len: usize) -> LearningResult<Vec<Vec<f64>>> {
+    if parameters.is_empty() {
+        return Ok(Vec::new());
+    }
+    let values = sized_parameter(parameters, name, num_rows * row_len)?;
+    Ok((0..num_rows).map(|i| values[i * row_len..(i + 1) * row_len].to_vec()).collect())
+}
+
+/// Scales the non negative values to a sum of 1, None if they are all 0 or invalid.
+fn normalize(mut values: Vec<f64>) -> Option<Vec<f64>> {
+    let sum: f64 = values.iter().sum();
+    if !(sum > 0.0 && sum.is_finite()) {
+        return None;
+    }
+    for x in &mut values {
+        *x /= sum;
+    }
+    Some(values)
+}
+
+fn log_sum_exp(values: &[f64]) -> f64 {
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    if !max.is_finite() {
+        return max;
+    }
+    max + values.iter().map(|x| (x - max).exp()).sum::<f64>().ln()
+}
+
+/// Probabilities from log likelihoods, shifted by the maximum to avoid underflow.
+fn softmax(log_likelihoods: &[f64]) -> Option<Vec<f64>> {
+    let max = log_likelihoods.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    if !max.is_finite() {
+        return None;
+    }
+    normalize(log_likelihoods.iter().map(|x| (x - max).exp()).collect())
+}
+
+fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norms = a.iter().map(|x| x * x).sum::<f64>().sqrt() * b.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norms > 0.0 { dot / norms } else { 0.0 }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // three noisy clusters around different pitch class profiles
+    fn examples(num_per_label: usize, seed: f64) -> (Matrix<f64>, Vec<usize>) {
+        let profiles = [[0, 4, 7], [2, 7, 11], [9, 1, 4]];
+        let mut data = Vec::new();
+        let mut labels = Vec::new();
+        for i in 0..num_per_label {
+            for (label_i, profile) in profiles.iter().enumerate() {
+                data.extend((0..12).map(|pc| {
+                    let noise = 0.05 * ((seed + (i * 12 + pc) as f64 * (label_i + 1) as f64) * 12.9898).sin();
+                    if profile.contains(&pc) { 0.3 + noise } else { 0.01 + noise.abs() * 0.2 }
+                }));
+                labels.push(label_i);
+            }
+        }
+        (Matrix::new(labels.len(), 12, data), labels)
+    }
+
+    fn accuracy<C: Classifier>(mut classifier: C) -> f64 {
+        let (inputs, labels) = examples(20, 0.0);
+        classifier.train(&inputs, &labels, 3).unwrap();
+        let (test_inputs, test_labels) = examples(10, 100.0);
+        let correct = test_inputs.iter_rows().zip(&test_labels).filter(|&(features, &label_i)| {
+            match classifier.probabilities(features) {
+                Some(probabilities) => {
+                    assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-9, "{:?}", probabilities);
+                    (0..3).all(|i| i == label_i || probabilities[i] < probabilities[label_i])
+                }
+                None => false,
+            }
+        }).count();
+        correct as f64 / test_labels.len() as f64
+    }
+
+    #[test]
+    fn untrained_classifiers_have_no_probabilities() {
+        let features = [0.1; 12];
+        assert_eq!(NaiveBayesClassifier::default().probabilities(&features), None);
+        assert_eq!(LogisticRegressionClassifier::default().probabilities(&features), None);
+        assert_eq!(GaussianMixtureClassifier::default().probabilities(&features), None);
+        assert_eq!(NearestTemplateClassifier::default().probabilities(&features), None);
+        assert_eq!(KNearestNeighboursClassifier::default().probabilities(&features), None);
+        assert_eq!(NeuralNetClassifier::default().probabilities(&features), None);
+    }
+
+    #[test]
+    fn features_of_another_size_have_no_probabilities() {
+        let classifiers: Vec<Box<dyn Classifier>> = vec![
+            Box::new(NaiveBayesClassifier::default()),
+            Box::new(LogisticRegressionClassifier::default()),
+            Box::new(SvmClassifier::default()),
+            Box::new(NeuralNetClassifier::default()),
+            Box::new(GaussianMixtureClassifier::default()),
+            Box::new(NearestTemplateClassifier::default()),
+            Box::new(KNearestNeighboursClassifier::default()),
+        ];
+        let (inputs, labels) = examples(20, 0.0);
+        for mut classifier in classifiers {
+            classifier.train(&inputs, &labels, 3).unwrap();
+            assert_eq!(classifier.num_features(), Some(12), "{}", classifier.kind());
+            assert!(classifier.probabilities(inputs.get_row(0).unwrap()).is_some(), "{}", classifier.kind());
+            assert_eq!(classifier.probabilities(&inputs.get_row(0).unwrap()[..11]), None, "{}", classifier.kind());
+            assert_eq!(classifier.probabilities(&[0.1; 13]), None, "{}", classifier.kind());
+        }
+    }
+
+    #[test]
+    fn classifiers_separate_chords() {
+        assert_eq!(accuracy(NaiveBayesClassifier::default()), 1.0);
+        assert_eq!(accuracy(NearestTemplateClassifier::default()), 1.0);
+        assert_eq!(accuracy(KNearestNeighboursClassifier::default()), 1.0);
+        assert_eq!(accuracy(GaussianMixtureClassifier::default()), 1.0);
+        assert_eq!(accuracy(SvmClassifier::default()), 1.0);
+        assert_eq!(accuracy(NeuralNetClassifier::default()), 1.0);
+        assert!(accuracy(LogisticRegressionClassifier::default()) > 0.9);
+    }
+
+    fn assert_restored<C: Classifier + Default>(mut trained: C) {
+        let (inputs, labels) = examples(20, 0.0);
+        trained.train(&inputs, &labels, 3).unwrap();
+        let mut restored = C::default();
+        restored.set_parameters(&trained.parameters(), 12, 3).unwrap();
+        assert_eq!(restored.parameters(), trained.parameters());
+        let (test_inputs, _) = examples(5, 100.0);
+        for features in test_inputs.iter_rows() {
+            assert_eq!(restored.probabilities(features), trained.probabilities(features), "{}", trained.kind());
+        }
+
+        assert!(restored.set_parameters(&trained.parameters(), 11, 3).is_err(), "{}", trained.kind());
+        assert_eq!(restored.probabilities(&[0.1; 12]), None);
+        restored.set_parameters(&[], 12, 3).unwrap();
+        assert_eq!(restored.parameters(), vec![]);
+    }
+
+    #[test]
+    fn parameters_restore_the_model() {
+        assert_restored(NaiveBayesClassifier::default());
+        assert_restored(NearestTemplateClassifier::default());
+        assert_restored(KNearestNeighboursClassifier::default());
+        assert_restored(GaussianMixtureClassifier::new(2));
+        assert_restored(SvmClassifier::default());
+        assert_restored(NeuralNetClassifier::default());
+        assert_restored(LogisticRegressionClassifier::default());
+    }
+}
@@ -0,0 +1,195 @@
+use std::f64;
+use std::f64::consts::PI;
+
+use rustfft::FFTplanner;
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+
+use sample::Sample;
+use spectrum_analyzer::SpectrumAnalyzer;
+use tuning::Tuning;
+use window::WindowFunction;
+
+#[derive(Clone, Debug)]
+pub struct ConstantQInitProps {
+    pub sample_rate: usize,
+    /// Center frequency of the lowest bin
+    pub min_frequency: f64,
+    pub bins_per_octave: usize,
+    pub num_octaves: usize,
+    /// Spectral kernel values below this fraction of the kernel maximum are ignored
+    pub sparsity_threshold: f64,
+    /// Window of the temporal kernels
+    pub window: WindowFunction,
+}
+
+impl Default for ConstantQInitProps {
+    fn default() -> Self {
+        Self {
+            sample_rate: 44_100,
+            min_frequency: 65.406_391_93, // C2
+            bins_per_octave: 36,
+            num_octaves: 5,
+            sparsity_threshold: 0.01,
+            window: WindowFunction::Hamming,
+        }
+    }
+}
+
+/// Log-frequency spectrum, where every bin has the same ratio of center frequency to bandwidth.
+///
+/// Implemented with precomputed sparse spectral kernels (Brown and Puckette, 1992),
+/// so processing a frame needs one FFT and a few multiplications per bin.
+pub struct ConstantQ {
+    props: ConstantQInitProps,
+    frequencies: Vec<f64>,
+    kernels: Vec<Vec<(usize, Complex<f64>)>>,
+    spectrum_analyzer: SpectrumAnalyzer,
+    /// Magnitude of every bin, starting at min_frequency
+    pub spectrum: Vec<f64>,
+}
+
+impl ConstantQ {
+    pub fn new(props: ConstantQInitProps) -> Self {
+        let num_bins = props.bins_per_octave * props.num_octaves;
+        let q = 1.0 / (f64::powf(2.0, 1.0 / props.bins_per_octave as f64) - 1.0);
+        let frequencies: Vec<f64> = (0..num_bins)
+            .map(|k| props.min_frequency * f64::powf(2.0, k as f64 / props.bins_per_octave as f64))
+            .collect();
+
+        let longest_window = (q * props.sample_rate as f64 / props.min_frequency).ceil() as usize;
+        let fft_size = longest_window.next_power_of_two();
+        let fft = FFTplanner::new(false).plan_fft(fft_size);
+
+        let mut temporal_kernel = vec![Complex::zero(); fft_size];
+        let mut spectral_kernel = vec![Complex::zero(); fft_size];
+        let kernels = frequencies.iter()
+            .map(|&frequency| {
+                // window centered in the frame, so that all bins share the same time reference
+                let window_length = (q * props.sample_rate as f64 / frequency).ceil() as usize;
+                let start = (fft_size - window_length) / 2;
+                for c in temporal_kernel.iter_mut() {
+                    *c = Complex::zero();
+                }
+                for n in 0..window_length {
+                    let window = props.window.value(n, window_length);
+                    let phase = 2.0 * PI * q * n as f64 / window_length as f64;
+                    temporal_kernel[start + n] = Complex::new(phase.cos(), phase.sin()) * (window / window_length as f64);
+                }
+                fft.process(&mut temporal_kernel, &mut spectral_kernel);
+
+                let max = spectral_kernel.iter().map(|c| c.norm()).fold(0.0, f64::max);
+                spectral_kernel.iter().enumerate()
+                    .filter(|&(_, c)| c.norm() >= max * props.sparsity_threshold)
+                    .map(|(j, c)| (j, c.conj() / fft_size as f64))
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            frequencies,
+            kernels,
+            spectrum_analyzer: SpectrumAnalyzer::new(fft_size),
+            spectrum: vec![0.0; num_bins],
+            props,
+        }
+    }
+
+    /// Number of samples, which are analysed by `process_audio_frame`.
+    pub fn fft_size(&self) -> usize {
+        self.spectrum_analyzer.size()
+    }
+
+    pub fn bins_per_octave(&self) -> usize {
+        self.props.bins_per_octave
+    }
+
+    /// Center frequency of every bin
+    pub fn frequencies(&self) -> &[f64] {
+        &self.frequencies
+    }
+
+    /// Calculates the spectrum of a frame with `fft_size` samples.
+    /// Shorter frames are zero padded, extra samples are ignored.
+    pub fn process_audio_frame<S: Sample>(&mut self, frame: &[S]) {
+        let fft_out = self.spectrum_analyzer.process(frame.iter().cloned());
+        for (magnitude, kernel) in self.spectrum.iter_mut().zip(&self.kernels) {
+            let bin = kernel.iter().fold(Complex::zero(), |sum, &(j, k)| sum + fft_out[j] * k);
+            *magnitude = bin.norm();
+        }
+    }
+
+    /// Sums the spectrum into chroma bins, see `fold_to_chroma`.
+    pub fn fold_to_chroma(&self, tuning: Tuning, chroma: &mut [f64]) {
+        fold_to_chroma(&self.spectrum, &self.frequencies, tuning, chroma);
+    }
+}
+
+/// Sums the magnitudes of a log-frequency spectrum into pitch classes.
+///
+/// Bin 0 of the chroma is C in the given tuning. The chroma length may be 12 or a multiple
+/// of it (e.g. 36 for three bins per semitone).
+pub fn fold_to_chroma(spectrum: &[f64], frequencies: &[f64], tuning: Tuning, chroma: &mut [f64]) {
+    let num_classes = chroma.len() as f64;
+    let c0 = tuning.note_frequency(12.0);
+    for x in chroma.iter_mut() {
+        *x = 0.0;
+    }
+    for (&magnitude, &frequency) in spectrum.iter().zip(frequencies) {
+        let pitch_class = (num_classes * (frequency / c0).log2()).round().rem_euclid(num_classes) as usize;
+        chroma[pitch_class] += magnitude;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: usize = 11_025;
+
+    fn test_props() -> ConstantQInitProps {
+        ConstantQInitProps { sample_rate: SAMPLE_RATE, num_octaves: 3, ..Default::default() }
+    }
+
+    fn sines(frequencies: &[f64], length: usize) -> Vec<f64> {
+        (0..length)
+            .map(|i| frequencies.iter().map(|f| (2.0 * PI * f * i as f64 / SAMPLE_RATE as f64).sin()).sum())
+            .collect()
+    }
+
+    fn strongest_bin(spectrum: &[f64]) -> usize {
+        (0..spectrum.len()).max_by(|&a, &b| spectrum[a].partial_cmp(&spectrum[b]).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn resolves_low_notes() {
+        let mut constant_q = ConstantQ::new(test_props());
+        let fft_size = constant_q.fft_size();
+
+        // E2, lowest guitar string, is 4 semitones above C2
+        constant_q.process_audio_frame(&sines(&[82.41], fft_size));
+        let peak = strongest_bin(&constant_q.spectrum);
+        assert_eq!(peak, 12);
+        for &neighbour in &[peak - 3, peak + 3] {
+            assert!(constant_q.spectrum[neighbour] < 0.1 * constant_q.spectrum[peak]);
+        }
+    }
+
+    #[test]
+    fn folds_to_chroma() {
+        let mut constant_q = ConstantQ::new(ConstantQInitProps { bins_per_octave: 12, ..test_props() });
+        let fft_size = constant_q.fft_size();
+
+        // G2, B2, D3
+        constant_q.process_audio_frame(&sines(&[98.0, 123.47, 146.83], fft_size));
+        let mut chroma = vec![0.0; 12];
+        constant_q.fold_to_chroma(Tuning::default(), &mut chroma);
+
+        let mut strongest: Vec<usize> = (0..12).collect();
+        strongest.sort_by(|&a, &b| chroma[b].partial_cmp(&chroma[a]).unwrap());
+        strongest.truncate(3);
+        strongest.sort();
+        assert_eq!(strongest, vec![2, 7, 11]);
+    }
+}
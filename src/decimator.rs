@@ -0,0 +1,175 @@
+use std::f64;
+use std::f64::consts::PI;
+
+use sample::Sample;
+
+// Order of the Butterworth anti-aliasing filter, must be even.
+const FILTER_ORDER: usize = 8;
+// Cutoff frequency relative to the Nyquist frequency after decimation.
+const CUTOFF_RATIO: f64 = 0.8;
+
+/// Second order IIR filter section, which keeps its state between calls of `process`.
+#[derive(Clone, Debug)]
+pub struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    x_1: f64,
+    x_2: f64,
+    y_1: f64,
+    y_2: f64,
+}
+
+impl Biquad {
+    /// Low pass filter (RBJ audio EQ cookbook) with the cutoff given as fraction of the sample rate.
+    pub fn low_pass(cutoff: f64, q: f64) -> Self {
+        let w0 = 2.0 * PI * cutoff;
+        let cos_w0 = w0.cos();
+        let alpha = w0.sin() / (2.0 * q);
+        let a0 = 1.0 + alpha;
+
+        Biquad {
+            b0: (1.0 - cos_w0) / 2.0 / a0,
+            b1: (1.0 - cos_w0) / a0,
+            b2: (1.0 - cos_w0) / 2.0 / a0,
+            a1: -2.0 * cos_w0 / a0,
+            a2: (1.0 - alpha) / a0,
+            x_1: 0.0,
+            x_2: 0.0,
+            y_1: 0.0,
+            y_2: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, x: f64) -> f64 {
+        let y = x * self.b0 + self.x_1 * self.b1 + self.x_2 * self.b2 - self.y_1 * self.a1 - self.y_2 * self.a2;
+        self.x_2 = self.x_1;
+        self.x_1 = x;
+        self.y_2 = self.y_1;
+        self.y_1 = y;
+        y
+    }
+
+    pub fn reset(&mut self) {
+        self.x_1 = 0.0;
+        self.x_2 = 0.0;
+        self.y_1 = 0.0;
+        self.y_2 = 0.0;
+    }
+}
+
+/// Reduces the sample rate by an integer factor.
+///
+/// The input is low pass filtered by a Butterworth filter below the new Nyquist frequency
+/// before every factor-th sample is kept. The filter state and the decimation phase are kept
+/// between calls, so a signal can be processed frame by frame.
+#[derive(Clone, Debug)]
+pub struct Decimator {
+    factor: usize,
+    filters: Vec<Biquad>,
+    phase: usize,
+}
+
+impl Decimator {
+    pub fn new(factor: usize) -> Self {
+        assert!(factor > 0, "factor must be greater than 0");
+        let filters = if factor == 1 {
+            Vec::new()
+        } else {
+            let cutoff = CUTOFF_RATIO * 0.5 / factor as f64;
+            (0..FILTER_ORDER / 2)
+                .map(|k| {
+                    let theta = (2 * k + 1) as f64 * PI / (2 * FILTER_ORDER) as f64;
+                    Biquad::low_pass(cutoff, 1.0 / (2.0 * theta.cos()))
+                })
+                .collect()
+        };
+        Decimator { factor, filters, phase: 0 }
+    }
+
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    /// Filters the input and writes the decimated samples into the output.
+    /// Returns the number of written samples.
+    pub fn process<S: Sample>(&mut self, input: &[S], output: &mut [f64]) -> usize {
+        let mut written = 0;
+        for &x in input {
+            let y = self.filters.iter_mut().fold(x.to_f64(), |sample, filter| filter.process(sample));
+            if self.phase == 0 {
+                output[written] = y;
+                written += 1;
+            }
+            self.phase = (self.phase + 1) % self.factor;
+        }
+        written
+    }
+
+    pub fn reset(&mut self) {
+        for filter in &mut self.filters {
+            filter.reset();
+        }
+        self.phase = 0;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 44_100.0;
+
+    fn sine(frequency: f64, length: usize) -> Vec<f64> {
+        (0..length).map(|i| (2.0 * PI * frequency * i as f64 / SAMPLE_RATE).sin()).collect()
+    }
+
+    // RMS after the filter has settled
+    fn decimated_rms(factor: usize, frequency: f64) -> f64 {
+        let input = sine(frequency, 8192 * factor);
+        let mut output = vec![0.0; input.len() / factor];
+        Decimator::new(factor).process(&input, &mut output);
+        let settled = &output[output.len() / 2..];
+        (settled.iter().map(|x| x * x).sum::<f64>() / settled.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn keeps_pass_band() {
+        for &factor in &[2, 4, 8] {
+            let rms = decimated_rms(factor, 440.0);
+            assert!((rms - 0.5f64.sqrt()).abs() < 0.01, "factor = {}, rms = {}", factor, rms);
+        }
+    }
+
+    #[test]
+    fn suppresses_aliases() {
+        for &factor in &[2, 4, 8] {
+            // would alias to 1/4 of the new sample rate without filtering
+            let new_sample_rate = SAMPLE_RATE / factor as f64;
+            let rms = decimated_rms(factor, 0.75 * new_sample_rate);
+            let attenuation_db = 20.0 * (rms / 0.5f64.sqrt()).log10();
+            assert!(attenuation_db < -40.0, "factor = {}, attenuation = {} dB", factor, attenuation_db);
+        }
+    }
+
+    #[test]
+    fn processing_in_frames_equals_processing_at_once() {
+        let input = sine(1000.0, 1000);
+
+        let mut output_at_once = vec![0.0; 250];
+        Decimator::new(4).process(&input, &mut output_at_once);
+
+        let mut decimator = Decimator::new(4);
+        let mut output_in_frames = vec![0.0; 250];
+        let mut written = 0;
+        for frame in input.chunks(30) {
+            written += decimator.process(frame, &mut output_in_frames[written..]);
+        }
+
+        assert_eq!(written, 250);
+        assert_eq!(output_at_once, output_in_frames);
+    }
+}
@@ -0,0 +1,55 @@
+use std::f64;
+use std::f64::consts::PI;
+
+/// Magnitude of the DFT of a block of samples at a single frequency.
+///
+/// The coefficients are calculated once in `new`, so a filter can be reused for every block.
+/// The frequency doesn't need to be a multiple of sample_rate / block length.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GoertzelFilter {
+    sine: f64,
+    cosine: f64,
+    term_coefficient: f64,
+}
+
+impl GoertzelFilter {
+    pub fn new(frequency: f64, sample_rate: f64) -> Self {
+        let omega = 2.0 * PI * frequency / sample_rate;
+        Self {
+            sine: omega.sin(),
+            cosine: omega.cos(),
+            term_coefficient: 2.0 * omega.cos(),
+        }
+    }
+
+    pub fn magnitude(&self, samples: &[f64]) -> f64 {
+        let (mut prev, mut prevprev) = (0.0, 0.0);
+        for &sample in samples {
+            let this = self.term_coefficient * prev - prevprev + sample;
+            prevprev = prev;
+            prev = this;
+        }
+        let real = prev - prevprev * self.cosine;
+        let imag = prevprev * self.sine;
+        (real * real + imag * imag).sqrt()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equals_dft() {
+        let (sample_rate, frequency) = (8000.0, 697.3);
+        let samples: Vec<f64> = (0..300).map(|i| (2.0 * PI * 700.0 * i as f64 / sample_rate).sin()).collect();
+        let (re, im) = samples.iter().enumerate().fold((0.0, 0.0), |(re, im), (n, &x)| {
+            let phase = 2.0 * PI * frequency * n as f64 / sample_rate;
+            (re + x * phase.cos(), im - x * phase.sin())
+        });
+
+        let magnitude = GoertzelFilter::new(frequency, sample_rate).magnitude(&samples);
+        assert!((magnitude - (re * re + im * im).sqrt()).abs() < 1e-9);
+    }
+}
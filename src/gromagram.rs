@@ -1,6 +1,20 @@
 use std::f64;
-use goertzel::Parameters;
+use pitch_calc::{LetterOctave, Step};
 
+use chroma_frame::ChromaFrame;
+use goertzel_filter::GoertzelFilter;
+use normalization::Normalization;
+use sample::Sample;
+use sliding_dft::SlidingDft;
+use spectrum_analyzer::SpectrumAnalyzer;
+use template_chord_detection::PITCH_CLASS_NAMES;
+use tuning::{Tuning, TuningEstimator, TuningEstimatorInitProps};
+use window::WindowFunction;
+
+const NUM_NOTES_IN_OCTAVE: usize = 12;
+/// An estimated tuning is applied only if it differs more from the current one,
+/// because retuning rebuilds every sliding DFT from the whole buffer
+const RETUNE_THRESHOLD_CENTS: f64 = 1.0;
 
 #[derive(Clone)]
 pub struct GromagramInitProps {
@@ -8,6 +22,40 @@ pub struct GromagramInitProps {
     pub sample_rate: u32,
     pub start_note: usize,
     pub notes_count: usize,
+    /// Estimate the tuning from the spectrum of the window and retune the notes accordingly,
+    /// once the estimate differs by more than 1 cent from the current tuning
+    pub auto_tuning: bool,
+    pub window: WindowFunction,
+    /// Number of samples between the frames yielded by `Gromagram::frames`
+    pub hop_size: usize,
+    /// MIDI notes [bass_start_note, bass_start_note + bass_notes_count) are folded into the bass gromagram.
+    /// Low notes need a long window to be resolved, so no bass notes are analysed by default.
+    pub bass_start_note: usize,
+    pub bass_notes_count: usize,
+    /// Update the note magnitudes per sample by a sliding DFT instead of running a Goertzel filter
+    /// over the whole window for every frame. Cheaper for small frames, but the window is always rectangular.
+    pub sliding: bool,
+    /// Frames whose RMS over the window (full scale = 1) is at most this threshold are silent,
+    /// and their gromagrams are set to 0
+    pub silence_threshold: f64,
+    /// Applied to the gromagram and the bass gromagram of every frame
+    pub normalization: Normalization,
+    /// Analyse every note with the shortest window, which resolves neighbouring semitones
+    /// (constant Q), limited to window_size. Low notes get long windows, high notes short ones.
+    pub multi_resolution: bool,
+    /// Number of frequencies analysed per semitone, which are reduced to one bin per semitone,
+    /// so that the energy of mistuned notes isn't lost between the bins
+    pub bins_per_semitone: usize,
+    pub semitone_reduction: SemitoneReduction,
+}
+
+/// How the magnitudes of the bins of a semitone are reduced to one value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SemitoneReduction {
+    /// The largest magnitude
+    Peak,
+    /// Magnitude weighted mean of the magnitudes, sum(m^2) / sum(m)
+    WeightedMean,
 }
 
 impl Default for GromagramInitProps {
@@ -17,60 +65,624 @@ impl Default for GromagramInitProps {
             sample_rate: 44_100,
             start_note: 28,     // e2 82.41 Hz lowest guitar string
             notes_count: 12,    // one octave
+            auto_tuning: false,
+            window: WindowFunction::Rectangular,
+            hop_size: 1024,
+            bass_start_note: 28,
+            bass_notes_count: 0,
+            sliding: false,
+            silence_threshold: 0.0,
+            normalization: Normalization::None,
+            multi_resolution: false,
+            bins_per_semitone: 1,
+            semitone_reduction: SemitoneReduction::Peak,
         }
     }
 }
 
+/// The note analysed by a bin of the gromagram.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoteBin {
+    pub midi_note: usize,
+    /// Frequency of the note in the current tuning
+    pub frequency: f64,
+    pub letter_octave: LetterOctave,
+    /// Number of samples analysed for this note
+    pub window_size: usize,
+}
+
+impl NoteBin {
+    /// Scientific pitch notation, e.g. "C#4"
+    pub fn name(&self) -> String {
+        format!("{}{}", PITCH_CLASS_NAMES[self.midi_note % NUM_NOTES_IN_OCTAVE], self.letter_octave.octave())
+    }
+}
+
 pub struct Gromagram {
     pub props: GromagramInitProps,
-    buffer: Vec<i16>,
+    buffer: Vec<f64>,
     buffer_pos: usize,
+    // sum of the squared samples in the buffer, recalculated on every wrap around to avoid drift
+    energy: f64,
+    silent: bool,
+    window: Vec<f64>,
+    windowed_buffer: Vec<f64>,
+    // number of the latest samples in windowed_buffer, 0 if it's outdated
+    windowed_len: usize,
     pub gromagram: Vec<f64>,
+    /// 12 bins, index 0 = C
+    pub bass_gromagram: Vec<f64>,
+    tuning: Tuning,
+    tuning_estimator: TuningEstimator,
+    spectrum_analyzer: Option<SpectrumAnalyzer>,
+    // deviation of the last frame from the tuning in cents, estimated from the sub semitone bins
+    tuning_deviation: f64,
+    // one per sub semitone bin of the notes, followed by the bass notes
+    goertzel_filters: Vec<GoertzelFilter>,
+    sliding_dfts: Vec<SlidingDft>,
+    note_window_sizes: Vec<usize>,
+    // only used in multi resolution mode
+    note_windows: Vec<Vec<f64>>,
 }
 
 impl Gromagram {
     pub fn new(props: GromagramInitProps) -> Self {
-        let chromagram = Self {
-            buffer: vec![0; props.window_size],
-            gromagram: vec![0.0; props.notes_count as usize],
+        assert!(props.bins_per_semitone > 0, "bins_per_semitone must be greater than 0");
+        let note_window_sizes: Vec<usize> = pitches(&props)
+            .map(|pitch| if props.multi_resolution {
+                semitone_resolving_window_size(Tuning::default().note_frequency(pitch), props.sample_rate as f64)
+                    .min(props.window_size)
+            } else {
+                props.window_size
+            })
+            .collect();
+        let note_windows = if props.multi_resolution {
+            note_window_sizes.iter().map(|&size| props.window.generate(size)).collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut gromagram = Self {
+            buffer: vec![0.0; props.window_size],
+            gromagram: vec![0.0; props.notes_count],
+            bass_gromagram: vec![0.0; NUM_NOTES_IN_OCTAVE],
             buffer_pos: 0,
+            energy: 0.0,
+            silent: true,
+            window: props.window.generate(props.window_size),
+            windowed_buffer: vec![0.0; props.window_size],
+            windowed_len: 0,
+            tuning: Tuning::default(),
+            tuning_deviation: 0.0,
+            tuning_estimator: TuningEstimator::new(TuningEstimatorInitProps::default()),
+            spectrum_analyzer: if props.auto_tuning { Some(SpectrumAnalyzer::new(props.window_size)) } else { None },
+            goertzel_filters: Vec::new(),
+            sliding_dfts: Vec::new(),
+            note_window_sizes,
+            note_windows,
             props,
         };
-
-        chromagram
+        gromagram.update_coefficients();
+        gromagram
     }
 
     pub fn reset(&mut self) {
         for p in &mut self.buffer {
-            *p = 0;
+            *p = 0.0;
         }
         self.buffer_pos = 0;
+        self.energy = 0.0;
+        for sliding_dft in &mut self.sliding_dfts {
+            sliding_dft.reset();
+        }
     }
 
-    pub fn process_audio_frame(&mut self, frame: &[i16]) {
+    pub fn process_audio_frame<S: Sample>(&mut self, frame: &[S]) {
+        let buffer_len = self.buffer.len();
         for &input in frame {
+            let input = input.to_f64();
+            let old_sample = self.buffer[self.buffer_pos];
+            if self.props.sliding {
+                for (sliding_dft, &window_size) in self.sliding_dfts.iter_mut().zip(&self.note_window_sizes) {
+                    let old_note_sample = self.buffer[(self.buffer_pos + buffer_len - window_size) % buffer_len];
+                    sliding_dft.update(input, old_note_sample);
+                }
+            }
+            self.energy += input * input - old_sample * old_sample;
             self.buffer[self.buffer_pos] = input;
             self.buffer_pos = (self.buffer_pos + 1) % self.buffer.len();
+            if self.buffer_pos == 0 {
+                self.energy = self.buffer.iter().map(|x| x * x).sum();
+            }
+        }
+
+        let rms = (self.energy.max(0.0) / self.buffer.len() as f64).sqrt();
+        self.silent = rms <= self.props.silence_threshold;
+        if self.silent {
+            for x in self.gromagram.iter_mut().chain(self.bass_gromagram.iter_mut()) {
+                *x = 0.0;
+            }
+            self.tuning_deviation = 0.0;
+            return;
         }
 
+        self.estimate_tuning();
+        self.windowed_len = 0;
+
+        // circular mean of the sub semitone bin positions, weighted by their magnitudes
+        let bins_per_semitone = self.props.bins_per_semitone;
+        let (mut deviation_re, mut deviation_im) = (0.0, 0.0);
         for i in 0..self.props.notes_count {
-            let note = self.props.start_note + i;
-            let note_freq = f64::powf(2.0, (note as f64 - 69.0) / 12.0) * 440.0;
-            // eprintln!("note = {:?} {:?}", note, note_freq);
-            let gp = Parameters::new(note_freq as f32, self.props.sample_rate, self.buffer.len());
-            let goertzel_a = gp.start();
-            let a_mag = goertzel_a
-                .add(&self.buffer[self.buffer_pos..])
-                .add(&self.buffer[0..self.buffer_pos])
-                .finish_mag();
-            self.gromagram[i] = a_mag as f64;
+            let (mut max, mut sum, mut sum_of_squares) = (0.0, 0.0, 0.0);
+            for j in 0..bins_per_semitone {
+                let magnitude = self.note_magnitude(i * bins_per_semitone + j);
+                max = f64::max(max, magnitude);
+                sum += magnitude;
+                sum_of_squares += magnitude * magnitude;
+                let angle = 2.0 * f64::consts::PI * sub_semitone_offset(j, bins_per_semitone);
+                deviation_re += magnitude * angle.cos();
+                deviation_im += magnitude * angle.sin();
+            }
+            self.gromagram[i] = match self.props.semitone_reduction {
+                SemitoneReduction::Peak => max,
+                SemitoneReduction::WeightedMean => if sum > 0.0 { sum_of_squares / sum } else { 0.0 },
+            };
         }
+        self.tuning_deviation = if bins_per_semitone > 1 {
+            100.0 * deviation_im.atan2(deviation_re) / (2.0 * f64::consts::PI)
+        } else {
+            0.0
+        };
+
+        for x in &mut self.bass_gromagram {
+            *x = 0.0;
+        }
+        let bass_offset = self.props.notes_count * bins_per_semitone;
+        for i in 0..self.props.bass_notes_count {
+            let note = self.props.bass_start_note + i;
+            let magnitude = (0..bins_per_semitone)
+                .map(|j| self.note_magnitude(bass_offset + i * bins_per_semitone + j))
+                .fold(0.0, f64::max);
+            self.bass_gromagram[note % NUM_NOTES_IN_OCTAVE] += magnitude;
+        }
+
+        self.props.normalization.apply(&mut self.gromagram);
+        self.props.normalization.apply(&mut self.bass_gromagram);
+    }
+
+    /// Deviation of the notes in the last frame from the current tuning in cents, between -50 and 50.
+    /// Estimated from the sub semitone bins, so it's always 0 with one bin per semitone.
+    pub fn tuning_deviation(&self) -> f64 {
+        self.tuning_deviation
+    }
+
+    /// True if the window of the last processed frame was silent.
+    pub fn is_silent(&self) -> bool {
+        self.silent
+    }
+
+    // index into goertzel_filters and sliding_dfts
+    fn note_magnitude(&mut self, index: usize) -> f64 {
+        let window_size = self.note_window_sizes[index];
+        // shorter windows are scaled, so that a tone has the same magnitude in all windows
+        let scale = self.props.window_size as f64 / window_size as f64;
+        if self.props.sliding {
+            return self.sliding_dfts[index].magnitude() * scale;
+        }
+
+        if window_size != self.windowed_len {
+            let window = if self.props.multi_resolution { &self.note_windows[index] } else { &self.window };
+            let buffer_len = self.buffer.len();
+            let start = (self.buffer_pos + buffer_len - window_size) % buffer_len;
+            let samples = self.buffer[start..].iter().chain(&self.buffer[0..start]);
+            for ((windowed, &sample), &w) in self.windowed_buffer.iter_mut().zip(samples).zip(window) {
+                *windowed = sample * w;
+            }
+            self.windowed_len = window_size;
+        }
+
+        self.goertzel_filters[index].magnitude(&self.windowed_buffer[..window_size]) * scale
     }
 
+    /// Mean window size of all notes, the average centre of the analysed audio is
+    /// half of it before the latest sample. The windows of the single notes are given by `bins`.
+    pub fn effective_window_size(&self) -> f64 {
+        self.note_window_sizes().sum::<usize>() as f64 / self.props.notes_count.max(1) as f64
+    }
+
+    // the window size of every note of the gromagram, the sub semitone bins of a note share it
+    fn note_window_sizes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.note_window_sizes.chunks(self.props.bins_per_semitone).take(self.props.notes_count).map(|sizes| sizes[0])
+    }
+
+    /// Calculates the filter coefficients of all notes for the current tuning.
+    /// In sliding mode the DFTs are recalculated from the buffered samples.
+    fn update_coefficients(&mut self) {
+        let pitches = pitches(&self.props).zip(&self.note_window_sizes);
+        let sample_rate = self.props.sample_rate;
+        let tuning = self.tuning;
+
+        if self.props.sliding {
+            self.sliding_dfts.clear();
+            self.sliding_dfts.extend(pitches.map(|(pitch, &window_size)| {
+                SlidingDft::new(tuning.note_frequency(pitch), sample_rate as f64, window_size)
+            }));
+            let buffer_len = self.buffer.len();
+            for (sliding_dft, &window_size) in self.sliding_dfts.iter_mut().zip(&self.note_window_sizes) {
+                let start = (self.buffer_pos + buffer_len - window_size) % buffer_len;
+                for &sample in self.buffer[start..].iter().chain(&self.buffer[0..start]).take(window_size) {
+                    sliding_dft.update(sample, 0.0);
+                }
+            }
+        } else {
+            self.goertzel_filters.clear();
+            self.goertzel_filters.extend(pitches.map(|(pitch, _)| {
+                GoertzelFilter::new(tuning.note_frequency(pitch), sample_rate as f64)
+            }));
+        }
+    }
+
+    /// Processes a whole signal and yields a gromagram every `hop_size` samples.
+    /// The last frame may have a shorter hop.
+    pub fn frames<S: Sample, I: IntoIterator<Item = S>>(&mut self, samples: I) -> GromagramFrames<'_, I::IntoIter> {
+        GromagramFrames {
+            hop: Vec::with_capacity(self.props.hop_size),
+            gromagram: self,
+            samples: samples.into_iter(),
+            position: 0,
+        }
+    }
+
+    /// The note of every bin of `gromagram`.
+    pub fn bins(&self) -> Vec<NoteBin> {
+        (self.props.start_note..(self.props.start_note + self.props.notes_count))
+            .zip(self.note_window_sizes())
+            .map(|(midi_note, window_size)| NoteBin {
+                midi_note,
+                frequency: self.tuning.note_frequency(midi_note as f64),
+                letter_octave: Step(midi_note as f32).to_letter_octave(),
+                window_size,
+            })
+            .collect()
+    }
+
+    /// Sums the bins of all octaves into a 12 bin chroma, index 0 = C, like the `Chromagram`.
+    pub fn fold_to_chroma(&self, chroma: &mut [f64]) {
+        assert_eq!(chroma.len(), NUM_NOTES_IN_OCTAVE, "chroma must have 12 bins");
+        for x in chroma.iter_mut() {
+            *x = 0.0;
+        }
+        for (i, &magnitude) in self.gromagram.iter().enumerate() {
+            chroma[(self.props.start_note + i) % NUM_NOTES_IN_OCTAVE] += magnitude;
+        }
+    }
+
+    pub fn tuning(&self) -> Tuning {
+        self.tuning
+    }
+
+    /// Retunes the note frequencies, e.g. to a tuning estimated by a `TuningEstimator`.
+    pub fn set_tuning(&mut self, tuning: Tuning) {
+        if tuning != self.tuning {
+            self.tuning = tuning;
+            self.update_coefficients();
+        }
+    }
+
+    fn estimate_tuning(&mut self) {
+        let tuning = match self.spectrum_analyzer {
+            Some(ref mut spectrum_analyzer) => {
+                let samples = self.buffer[self.buffer_pos..].iter()
+                    .chain(&self.buffer[0..self.buffer_pos])
+                    .cloned();
+                let magnitude_spectrum = spectrum_analyzer.magnitude_spectrum(samples);
+                let bin_frequency = self.props.sample_rate as f64 / self.buffer.len() as f64;
+                self.tuning_estimator.process_magnitude_spectrum(magnitude_spectrum, bin_frequency);
+                self.tuning_estimator.tuning()
+            }
+            None => return,
+        };
+        if (tuning.cents - self.tuning.cents).abs() > RETUNE_THRESHOLD_CENTS {
+            self.set_tuning(tuning);
+        }
+    }
+
+    /// Normalizes the gromagram to a sum of 1, a silent gromagram stays 0.
     pub fn normalize(&mut self) {
-        let sum: f64 = self.gromagram.iter().sum();
-        for x in &mut self.gromagram {
-            *x = *x / sum;
+        Normalization::L1.apply(&mut self.gromagram);
+    }
+}
+
+// MIDI pitches of the sub semitone bins of the notes, followed by the bass notes
+fn pitches(props: &GromagramInitProps) -> impl Iterator<Item = f64> {
+    let bins_per_semitone = props.bins_per_semitone;
+    (props.start_note..(props.start_note + props.notes_count))
+        .chain(props.bass_start_note..(props.bass_start_note + props.bass_notes_count))
+        .flat_map(move |note| (0..bins_per_semitone).map(move |j| note as f64 + sub_semitone_offset(j, bins_per_semitone)))
+}
+
+/// Offset of the sub semitone bin j in semitones, the bins are centered around the note.
+fn sub_semitone_offset(j: usize, bins_per_semitone: usize) -> f64 {
+    (j as f64 - (bins_per_semitone - 1) as f64 / 2.0) / bins_per_semitone as f64
+}
+
+/// Length of a rectangular window, whose DFT at the frequency has its first zero at the next semitone.
+fn semitone_resolving_window_size(frequency: f64, sample_rate: f64) -> usize {
+    let semitone_distance = frequency * (f64::powf(2.0, 1.0 / NUM_NOTES_IN_OCTAVE as f64) - 1.0);
+    (sample_rate / semitone_distance).ceil() as usize
+}
+
+pub struct GromagramFrames<'a, I> {
+    gromagram: &'a mut Gromagram,
+    samples: I,
+    hop: Vec<f64>,
+    position: usize,
+}
+
+impl<'a, S: Sample, I: Iterator<Item = S>> Iterator for GromagramFrames<'a, I> {
+    type Item = ChromaFrame;
+
+    fn next(&mut self) -> Option<ChromaFrame> {
+        self.hop.clear();
+        self.hop.extend(self.samples.by_ref().take(self.gromagram.props.hop_size).map(Sample::to_f64));
+        if self.hop.is_empty() {
+            return None;
+        }
+
+        self.gromagram.process_audio_frame(&self.hop);
+        let end = (self.position + self.hop.len()) as f64;
+        let center = end - self.gromagram.effective_window_size() / 2.0;
+        let sample_rate = self.gromagram.props.sample_rate as f64;
+        let mut frame = ChromaFrame::new(self.position, center, sample_rate,
+                                         &self.gromagram.gromagram, &self.gromagram.bass_gromagram, self.gromagram.is_silent());
+        frame.bin_centers_secs.clear();
+        frame.bin_centers_secs.extend(self.gromagram.note_window_sizes().map(|window_size| (end - window_size as f64 / 2.0) / sample_rate));
+        self.position += self.hop.len();
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use super::*;
+
+    #[test]
+    fn auto_tuning() {
+        let props = GromagramInitProps { window_size: 4096, notes_count: 24, auto_tuning: true, ..Default::default() };
+        let sample_rate = props.sample_rate as f64;
+        let mut gromagram = Gromagram::new(props);
+
+        let tuning = Tuning::from_reference_frequency(432.0);
+        let frequency = tuning.note_frequency(45.0);
+        let signal: Vec<i16> = (0..4096)
+            .map(|i| ((2.0 * PI * frequency * i as f64 / sample_rate).sin() * 10_000.0) as i16)
+            .collect();
+        gromagram.process_audio_frame(&signal);
+
+        assert!((gromagram.tuning().cents - tuning.cents).abs() < 5.0, "tuning = {:?}", gromagram.tuning());
+        let strongest_note = (0..24).max_by(|&a, &b| gromagram.gromagram[a].partial_cmp(&gromagram.gromagram[b]).unwrap());
+        assert_eq!(strongest_note, Some(45 - 28));
+    }
+
+    #[test]
+    fn frames_over_signal() {
+        let props = GromagramInitProps { hop_size: 512, ..Default::default() };
+        let mut gromagram = Gromagram::new(props);
+        let signal = (0..2000).map(|i| ((2.0 * PI * 110.0 * i as f64 / 44_100.0).sin() * 10_000.0) as i16);
+
+        let frames: Vec<ChromaFrame> = gromagram.frames(signal).collect();
+        let start_samples: Vec<usize> = frames.iter().map(|frame| frame.start_sample).collect();
+        assert_eq!(start_samples, vec![0, 512, 1024, 1536]);
+        assert_eq!(frames[2].time_secs, 1024.0 / 44_100.0);
+        assert_eq!(frames[3].chroma, gromagram.gromagram);
+    }
+
+    #[test]
+    fn float_samples() {
+        let signal: Vec<f32> = (0..1024).map(|i| (2.0 * PI * 110.0 * i as f64 / 44_100.0).sin() as f32 * 0.3).collect();
+        let quantised: Vec<i16> = signal.iter().map(|&x| i16::from_f64(x as f64)).collect();
+        let mut float_gromagram = Gromagram::new(GromagramInitProps::default());
+        let mut i16_gromagram = Gromagram::new(GromagramInitProps::default());
+        float_gromagram.process_audio_frame(&signal);
+        i16_gromagram.process_audio_frame(&quantised);
+
+        for (a, b) in float_gromagram.gromagram.iter().zip(&i16_gromagram.gromagram) {
+            assert!((a - b).abs() < 1e-3 * a.max(1.0), "f32 = {}, i16 = {}", a, b);
+        }
+    }
+
+    #[test]
+    fn silence_gate() {
+        let props = GromagramInitProps { silence_threshold: 0.003, normalization: Normalization::Max, ..Default::default() };
+        let mut gromagram = Gromagram::new(props);
+
+        gromagram.process_audio_frame(&[0i16; 1024]);
+        assert!(gromagram.is_silent());
+        gromagram.normalize();
+        assert!(gromagram.gromagram.iter().all(|&x| x == 0.0));
+
+        let quiet: Vec<i16> = (0..1024).map(|i| ((i as f64 * 0.1).sin() * 50.0) as i16).collect();
+        gromagram.process_audio_frame(&quiet);
+        assert!(gromagram.is_silent());
+
+        let loud: Vec<i16> = quiet.iter().map(|&x| x * 100).collect();
+        gromagram.process_audio_frame(&loud);
+        assert!(!gromagram.is_silent());
+        assert_eq!(gromagram.gromagram.iter().cloned().fold(0.0, f64::max), 1.0);
+    }
+
+    #[test]
+    fn multi_resolution() {
+        let props = GromagramInitProps {
+            window_size: 16_384, start_note: 33, notes_count: 48, multi_resolution: true, hop_size: 16_384, ..Default::default()
+        };
+        let sample_rate = props.sample_rate as f64;
+        let mut gromagram = Gromagram::new(props);
+        let bins = gromagram.bins();
+        assert!(bins[0].window_size > 10_000 && bins[0].window_size < 16_384, "bins = {:?}", bins[0]);
+        assert!(bins[47].window_size < 2000, "bins = {:?}", bins[47]);
+
+        // A1 is resolved from A#1 by its long window, and has the same magnitude as A4 in its short window
+        let signal: Vec<i16> = (0..16_384)
+            .map(|i| [55.0, 440.0].iter().map(|f| (2.0 * PI * f * i as f64 / sample_rate).sin() * 5000.0).sum::<f64>() as i16)
+            .collect();
+        let frame = gromagram.frames(signal).next().unwrap();
+        assert!(frame.chroma[1] < 0.1 * frame.chroma[0], "chroma = {:?}", frame.chroma);
+        assert!((frame.chroma[36] / frame.chroma[0] - 1.0).abs() < 0.05, "chroma = {:?}", frame.chroma);
+
+        let center = 16_384.0 - gromagram.effective_window_size() / 2.0;
+        assert!((frame.center_secs - center / sample_rate).abs() < 1e-9);
+        assert!(frame.center_secs > 16_384.0 / 2.0 / sample_rate);
+        // every note is centred in its own window, which ends at the last sample
+        assert_eq!(frame.bin_centers_secs.len(), 48);
+        for (bin, &bin_center_secs) in bins.iter().zip(&frame.bin_centers_secs) {
+            assert!((bin_center_secs - (16_384.0 - bin.window_size as f64 / 2.0) / sample_rate).abs() < 1e-9);
+        }
+        assert!(frame.bin_centers_secs[0] < frame.center_secs && frame.center_secs < frame.bin_centers_secs[47]);
+    }
+
+    #[test]
+    fn sub_semitone_bins() {
+        let mistuned = Tuning::from_cents(-30.0);
+        let gromagram_of = |bins_per_semitone| {
+            let props = GromagramInitProps { window_size: 8192, start_note: 57, notes_count: 24, bins_per_semitone, ..Default::default() };
+            let sample_rate = props.sample_rate as f64;
+            let mut gromagram = Gromagram::new(props);
+            let frequency = mistuned.note_frequency(69.0);
+            let signal: Vec<i16> = (0..8192)
+                .map(|i| ((2.0 * PI * frequency * i as f64 / sample_rate).sin() * 10_000.0) as i16)
+                .collect();
+            gromagram.process_audio_frame(&signal);
+            gromagram
+        };
+
+        let semitones = gromagram_of(1);
+        let thirds = gromagram_of(3);
+        assert_eq!(semitones.tuning_deviation(), 0.0);
+        assert!(thirds.gromagram[12] > 1.5 * semitones.gromagram[12], "{} <= {}", thirds.gromagram[12], semitones.gromagram[12]);
+        assert!((thirds.tuning_deviation() - mistuned.cents).abs() < 10.0, "deviation = {}", thirds.tuning_deviation());
+        assert_eq!(thirds.bins().len(), 24);
+    }
+
+    #[test]
+    fn bin_labels() {
+        let gromagram = Gromagram::new(GromagramInitProps { start_note: 33, notes_count: 24, ..Default::default() });
+        let bins = gromagram.bins();
+        assert_eq!(bins.len(), 24);
+        assert_eq!(bins[0].name(), "A1");
+        assert!((bins[0].frequency - 55.0).abs() < 1e-9);
+        assert_eq!(bins[3].name(), "C2");
+        assert_eq!(bins[4].letter_octave, LetterOctave(::pitch_calc::Letter::Db, 2));
+        assert_eq!(bins[4].name(), "C#2");
+    }
+
+    #[test]
+    fn fold_to_chroma() {
+        let props = GromagramInitProps { window_size: 4096, start_note: 48, notes_count: 24, ..Default::default() };
+        let sample_rate = props.sample_rate as f64;
+        let mut gromagram = Gromagram::new(props);
+        // G3 and D4
+        let signal: Vec<i16> = (0..4096)
+            .map(|i| [196.0, 293.66].iter().map(|f| (2.0 * PI * f * i as f64 / sample_rate).sin() * 10_000.0).sum::<f64>() as i16)
+            .collect();
+        gromagram.process_audio_frame(&signal);
+
+        let mut chroma = vec![0.0; 12];
+        gromagram.fold_to_chroma(&mut chroma);
+        let mut strongest: Vec<usize> = (0..12).collect();
+        strongest.sort_by(|&a, &b| chroma[b].partial_cmp(&chroma[a]).unwrap());
+        strongest.truncate(2);
+        strongest.sort();
+        assert_eq!(strongest, vec![2, 7]);
+    }
+
+    #[test]
+    fn bass_gromagram() {
+        let props = GromagramInitProps { window_size: 8192, bass_notes_count: 24, ..Default::default() };
+        let sample_rate = props.sample_rate as f64;
+        let mut gromagram = Gromagram::new(props);
+        // G1
+        let frequency = Tuning::default().note_frequency(31.0);
+        let signal: Vec<i16> = (0..8192)
+            .map(|i| ((2.0 * PI * frequency * i as f64 / sample_rate).sin() * 10_000.0) as i16)
+            .collect();
+        gromagram.process_audio_frame(&signal);
+
+        let strongest = (0..12).max_by(|&a, &b| gromagram.bass_gromagram[a].partial_cmp(&gromagram.bass_gromagram[b]).unwrap());
+        assert_eq!(strongest, Some(7));
+    }
+
+    #[test]
+    fn sliding_equals_goertzel() {
+        let props = GromagramInitProps { window_size: 2048, notes_count: 48, ..Default::default() };
+        let sample_rate = props.sample_rate as f64;
+        let mut goertzel = Gromagram::new(props.clone());
+        let mut sliding = Gromagram::new(GromagramInitProps { sliding: true, ..props });
+        sliding.set_tuning(Tuning::from_cents(20.0));
+        goertzel.set_tuning(Tuning::from_cents(20.0));
+
+        let signal: Vec<i16> = (0..5000)
+            .map(|i| ((2.0 * PI * 196.0 * i as f64 / sample_rate).sin() * 10_000.0) as i16)
+            .collect();
+        for frame in signal.chunks(256) {
+            goertzel.process_audio_frame(frame);
+            sliding.process_audio_frame(frame);
+        }
+
+        let max = goertzel.gromagram.iter().cloned().fold(0.0, f64::max);
+        for (a, b) in goertzel.gromagram.iter().zip(&sliding.gromagram) {
+            assert!((a - b).abs() < 1e-6 * max, "goertzel = {}, sliding = {}", a, b);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn auto_tuning_retunes_only_on_noticeable_changes() {
+        let props = GromagramInitProps { window_size: 2048, notes_count: 48, sliding: true, auto_tuning: true, ..Default::default() };
+        let sample_rate = props.sample_rate as f64;
+        let mut gromagram = Gromagram::new(props);
+
+        // G3 and D4 20 cents sharp with a slight vibrato, so that the estimate changes on every frame
+        let tuning = Tuning::from_cents(20.0);
+        let signal: Vec<f64> = (0..44_100)
+            .map(|i| {
+                let t = i as f64 / sample_rate;
+                let vibrato = 1.0 + 0.001 * (2.0 * PI * 5.0 * t).sin();
+                [55.0, 62.0].iter().map(|&note| 0.3 * (2.0 * PI * tuning.note_frequency(note) * vibrato * t).sin()).sum()
+            })
+            .collect();
+        let mut retunings = 0;
+        let mut last_tuning = gromagram.tuning();
+        let frames = signal.chunks(256);
+        let num_frames = frames.len();
+        for frame in frames {
+            gromagram.process_audio_frame(frame);
+            if gromagram.tuning() != last_tuning {
+                retunings += 1;
+                last_tuning = gromagram.tuning();
+            }
+        }
+        assert!(retunings < num_frames / 10, "{} retunings in {} frames", retunings, num_frames);
+        assert!((gromagram.tuning().cents - 20.0).abs() < 3.0, "tuning = {:?}", gromagram.tuning());
+    }
+
+    #[test]
+    fn window_reduces_leakage() {
+        let leakage = |window: WindowFunction| {
+            let props = GromagramInitProps { window_size: 4096, notes_count: 24, window, ..Default::default() };
+            let sample_rate = props.sample_rate as f64;
+            let mut gromagram = Gromagram::new(props);
+            let frequency = Tuning::default().note_frequency(45.0);
+            let signal: Vec<i16> = (0..4096)
+                .map(|i| ((2.0 * PI * frequency * i as f64 / sample_rate).sin() * 10_000.0) as i16)
+                .collect();
+            gromagram.process_audio_frame(&signal);
+            // a tritone above the tone
+            gromagram.gromagram[45 + 6 - 28] / gromagram.gromagram[45 - 28]
+        };
+
+        let rectangular = leakage(WindowFunction::Rectangular);
+        let blackman = leakage(WindowFunction::Blackman);
+        assert!(blackman < 0.1 * rectangular, "rectangular = {}, blackman = {}", rectangular, blackman);
+    }
+}
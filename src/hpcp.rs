@@ -0,0 +1,137 @@
+use std::f64;
+use std::f64::consts::PI;
+
+use spectral_peaks::spectral_peaks;
+
+/// Harmonic Pitch Class Profile (Gómez, 2006).
+#[derive(Clone, Debug, PartialEq)]
+pub struct HpcpInitProps {
+    /// Number of bins per octave, 12, 24 or 36
+    pub size: usize,
+    /// Only spectral peaks between min_frequency and max_frequency are considered
+    pub min_frequency: f64,
+    pub max_frequency: f64,
+    /// Peaks below this fraction of the strongest peak are ignored
+    pub peak_threshold: f64,
+    /// A peak also contributes to the pitch classes of the fundamentals it could be a harmonic of
+    pub num_harmonics: usize,
+    /// Weight of harmonic h is harmonic_decay^(h-1)
+    pub harmonic_decay: f64,
+    /// Width of the cosine weighting window in semitones
+    pub window_size: f64,
+}
+
+impl Default for HpcpInitProps {
+    fn default() -> Self {
+        Self {
+            size: 36,
+            min_frequency: 40.0,
+            max_frequency: 5000.0,
+            peak_threshold: 0.01,
+            num_harmonics: 4,
+            harmonic_decay: 0.6,
+            window_size: 4.0 / 3.0,
+        }
+    }
+}
+
+pub struct Hpcp {
+    props: HpcpInitProps,
+    pub hpcp: Vec<f64>,
+}
+
+impl Hpcp {
+    pub fn new(props: HpcpInitProps) -> Self {
+        Self {
+            hpcp: vec![0.0; props.size],
+            props,
+        }
+    }
+
+    /// Calculates the HPCP of a magnitude spectrum (bins 0 to N/2), normalized to a maximum of 1.
+    /// Bin 0 of the HPCP is centered at `reference_frequency`.
+    pub fn process_magnitude_spectrum(&mut self, magnitude_spectrum: &[f64], bin_frequency: f64, reference_frequency: f64) {
+        for x in &mut self.hpcp {
+            *x = 0.0;
+        }
+
+        let size = self.props.size as f64;
+        let bins_per_semitone = size / 12.0;
+        let half_window = self.props.window_size / 2.0;
+        let peaks = spectral_peaks(magnitude_spectrum, bin_frequency,
+                                   self.props.min_frequency, self.props.max_frequency, self.props.peak_threshold);
+
+        for peak in peaks {
+            let energy = peak.magnitude * peak.magnitude;
+            for harmonic in 1..(self.props.num_harmonics + 1) {
+                let harmonic_weight = self.props.harmonic_decay.powi(harmonic as i32 - 1);
+                let fundamental = peak.frequency / harmonic as f64;
+                // position of the fundamental in HPCP bins
+                let position = (size * (fundamental / reference_frequency).log2()).rem_euclid(size);
+
+                let window_bins = (half_window * bins_per_semitone).ceil() as isize;
+                let center_bin = position.round() as isize;
+                for bin in (center_bin - window_bins)..(center_bin + window_bins + 1) {
+                    let distance = (bin as f64 - position) / bins_per_semitone;
+                    if distance.abs() > half_window {
+                        continue;
+                    }
+                    let weight = (PI * distance / self.props.window_size).cos().powi(2);
+                    let hpcp_bin = bin.rem_euclid(self.props.size as isize) as usize;
+                    self.hpcp[hpcp_bin] += weight * harmonic_weight * energy;
+                }
+            }
+        }
+
+        let max = self.hpcp.iter().cloned().fold(0.0, f64::max);
+        if max > 0.0 {
+            for x in &mut self.hpcp {
+                *x /= max;
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use spectrum_analyzer::SpectrumAnalyzer;
+    use window::WindowFunction;
+    use super::*;
+
+    fn hann_spectrum(frequencies: &[f64], sample_rate: f64, fft_size: usize) -> Vec<f64> {
+        let mut analyzer = SpectrumAnalyzer::with_window_function(fft_size, WindowFunction::Hann);
+        let signal = (0..fft_size)
+            .map(|i| frequencies.iter().map(|f| (2.0 * PI * f * i as f64 / sample_rate).sin()).sum::<f64>());
+        analyzer.magnitude_spectrum(signal).to_vec()
+    }
+
+    #[test]
+    fn sub_semitone_resolution() {
+        let sample_rate = 44_100.0;
+        let mut hpcp = Hpcp::new(HpcpInitProps { num_harmonics: 1, ..Default::default() });
+        // A4 a third of a semitone sharp lands in the next 36-bin HPCP bin
+        let frequency = 440.0 * f64::powf(2.0, 1.0 / 36.0);
+        hpcp.process_magnitude_spectrum(&hann_spectrum(&[frequency], sample_rate, 8192), sample_rate / 8192.0, 261.63);
+
+        let strongest = (0..36).max_by(|&a, &b| hpcp.hpcp[a].partial_cmp(&hpcp.hpcp[b]).unwrap()).unwrap();
+        assert_eq!(strongest, 9 * 3 + 1);
+        assert_eq!(hpcp.hpcp[strongest], 1.0);
+    }
+
+    #[test]
+    fn twelve_bins() {
+        let sample_rate = 44_100.0;
+        let mut hpcp = Hpcp::new(HpcpInitProps { size: 12, ..Default::default() });
+        // D major: D, F#, A
+        let spectrum = hann_spectrum(&[293.66, 369.99, 440.0], sample_rate, 8192);
+        hpcp.process_magnitude_spectrum(&spectrum, sample_rate / 8192.0, 261.63);
+
+        for &pitch_class in &[2, 6, 9] {
+            assert!(hpcp.hpcp[pitch_class] > 0.5, "hpcp = {:?}", hpcp.hpcp);
+        }
+        for &pitch_class in &[0, 1, 4, 5, 8, 11] {
+            assert!(hpcp.hpcp[pitch_class] < 0.3, "hpcp = {:?}", hpcp.hpcp);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead};
+use std::str::FromStr;
+
+/// A labeled time span of a recording, one line of a `.lab` annotation file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment<L> {
+    /// Seconds
+    pub start: f64,
+    /// Seconds
+    pub end: f64,
+    pub label: L,
+}
+
+impl<L> Segment<L> {
+    /// The sample indices of the segment, clamped to the number of samples.
+    pub fn sample_range(&self, sample_rate: u32, samples_count: usize) -> (usize, usize) {
+        let to_index = |seconds: f64| ((seconds * sample_rate as f64).round().max(0.0) as usize).min(samples_count);
+        (to_index(self.start), to_index(self.end))
+    }
+}
+
+/// Reads annotations in the MIREX/Isophonics `.lab` format, one segment per line: `start end label`,
+/// separated by spaces or tabs, e.g. `2.612 4.865 A:min7/b3`. Empty lines are skipped.
+pub fn read_lab<L: FromStr, R: BufRead>(reader: R) -> Result<Vec<Segment<L>>, LabError> {
+    let mut segments = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let invalid = |reason: String| LabError::InvalidLine { line: i + 1, reason };
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() {
+            continue;
+        }
+        if fields.len() != 3 {
+            return Err(invalid(format!("expected start, end and label, got {:?}", line)));
+        }
+        let parse_time = |field: &str| match field.parse::<f64>() {
+            Ok(time) if time.is_finite() && time >= 0.0 => Ok(time),
+            _ => Err(invalid(format!("invalid time {:?}", field))),
+        };
+        let start = parse_time(fields[0])?;
+        let end = parse_time(fields[1])?;
+        if end < start {
+            return Err(invalid(format!("segment ends at {} before it starts at {}", end, start)));
+        }
+        let label = fields[2].parse().map_err(|_| invalid(format!("invalid label {:?}", fields[2])))?;
+        segments.push(Segment { start, end, label });
+    }
+    Ok(segments)
+}
+
+#[derive(Debug)]
+pub enum LabError {
+    Io(io::Error),
+    /// Line numbers start with 1
+    InvalidLine { line: usize, reason: String },
+}
+
+impl fmt::Display for LabError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LabError::Io(ref error) => write!(f, "Could not read annotations: {}", error),
+            LabError::InvalidLine { line, ref reason } => write!(f, "Invalid annotation in line {}: {}", line, reason),
+        }
+    }
+}
+
+impl Error for LabError {}
+
+impl From<io::Error> for LabError {
+    fn from(error: io::Error) -> Self {
+        LabError::Io(error)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chord::Chord;
+    use super::*;
+
+    #[test]
+    fn reads_isophonics_annotations() {
+        let lab = "0.000000 2.612267 N\n2.612267\t11.459070\tE\n\n11.459070 12.921927 A:min7/b3\n";
+        let segments: Vec<Segment<Chord>> = read_lab(lab.as_bytes()).unwrap();
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0].label, Chord::NoChord);
+        assert_eq!(segments[1], Segment { start: 2.612267, end: 11.459070, label: "E:maj".parse().unwrap() });
+        assert_eq!(segments[2].label.to_string(), "A:min7/b3");
+        assert_eq!(segments[1].sample_range(44_100, 200_000), (115_201, 200_000));
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        let invalid = ["1.0 2.0", "1.0 x C", "2.0 1.0 C", "1.0 2.0 H:maj", "0 1 C:maj extra"];
+        for &lab in &invalid {
+            match read_lab::<Chord, _>(lab.as_bytes()) {
+                Err(LabError::InvalidLine { line: 1, .. }) => {}
+                result => panic!("{:?} should be invalid, got {:?}", lab, result),
+            }
+        }
+        match read_lab::<Chord, _>("0 1 C\n1 2 ?".as_bytes()) {
+            Err(LabError::InvalidLine { line: 2, .. }) => {}
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+}
@@ -1,31 +1,46 @@
 extern crate rustfft;
-#[macro_use]
-extern crate lazy_static;
-extern crate goertzel;
+extern crate pitch_calc;
 extern crate rusty_machine;
 
+pub mod chord;
+pub mod chroma_frame;
+pub mod chroma_variants;
 pub mod chromagram;
+pub mod classifiers;
+pub mod constant_q;
+pub mod decimator;
+pub mod goertzel_filter;
 pub mod gromagram;
+pub mod hpcp;
+pub mod lab;
 pub mod chord_detection;
+pub mod template_chord_detection;
+pub mod tuning;
 pub mod midi_notes;
+pub mod nnls;
+pub mod normalization;
+pub mod nnls_chroma;
+pub mod sample;
+pub mod sliding_dft;
+pub mod spectral_peaks;
+pub mod spectrum_analyzer;
 pub mod utils;
+pub mod window;
 
-use rustfft::FFTplanner;
-use rustfft::num_complex::Complex;
-use rustfft::num_traits::Zero;
-use std::i16::MAX as I16_MAX;
+use sample::Sample;
+use spectrum_analyzer::SpectrumAnalyzer;
+use window::WindowFunction;
 
-pub fn calculate_spectrum(samples: &[i16]) -> Vec<f32> {
-    let mut input: Vec<Complex<f32>> = samples.iter().map(|&x| Complex::new(x as f32 / I16_MAX as f32, 0.0)).collect();
-//    println!("input = {:?}", input);
-    let mut output: Vec<Complex<f32>> = vec![Complex::zero(); input.len()];
-
-    let mut planner = FFTplanner::new(false);
-    let fft = planner.plan_fft(input.len());
-    fft.process(&mut input, &mut output);
+/// Power spectrum of the samples.
+/// Creates a new FFT plan on every call, use a `SpectrumAnalyzer` for repeated calculations.
+pub fn calculate_spectrum<S: Sample>(samples: &[S]) -> Vec<f64> {
+    calculate_windowed_spectrum(samples, WindowFunction::Rectangular)
+}
 
-//    println!("output = {:?}", output);
-    output.iter().map(|&c| c.norm_sqr()).collect()
+/// Power spectrum of the samples multiplied by the window function.
+pub fn calculate_windowed_spectrum<S: Sample>(samples: &[S], window: WindowFunction) -> Vec<f64> {
+    let mut analyzer = SpectrumAnalyzer::with_window_function(samples.len(), window);
+    analyzer.power_spectrum(samples.iter().cloned()).to_vec()
 }
 
 
@@ -33,18 +48,25 @@ pub fn calculate_spectrum(samples: &[i16]) -> Vec<f32> {
 mod tests {
     use std::f32::consts::PI;
     use calculate_spectrum;
-    use std::i16::MAX as I16_MAX;
 
     #[test]
     fn sin() {
         let length = 1024;
         let freq = 2.0;
-        let sin_vec: Vec<i16> = (0..length).map(|i| (((i as f32 * freq * 4.0 * PI / length as f32).sin() * 0.5 * (I16_MAX as f32)) as i16)).collect();
+        let sin_vec: Vec<i16> = (0..length).map(|i| ((i as f32 * freq * 4.0 * PI / length as f32).sin() * 0.5 * (i16::MAX as f32)) as i16).collect();
 //        println!("sin_vec = {:?}", sin_vec);
         let spectrum = calculate_spectrum(sin_vec.as_slice());
         println!("spectrum = {:?}", spectrum);
     }
 
+    #[test]
+    fn keeps_f64_precision() {
+        // the power of this signal is far below the smallest f32
+        let quiet: Vec<f64> = (0..64).map(|i| 1e-30 * (i as f64 * 2.0 * ::std::f64::consts::PI / 8.0).sin()).collect();
+        let spectrum = calculate_spectrum(&quiet);
+        assert!(spectrum[8] > 1e-60, "spectrum = {:?}", spectrum);
+    }
+
     #[test]
     fn it_works() {
 //        let spectrum = calculate_spectrum(vec![10, 10].as_slice());
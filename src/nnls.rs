@@ -0,0 +1,226 @@
+use std::f64;
+
+const TOLERANCE: f64 = 1e-10;
+
+/// Non-negative least squares solver (Lawson and Hanson, 1974) for a fixed matrix A.
+///
+/// Minimizes |Ax - b| subject to x >= 0. The Gram matrix A^T A is computed once,
+/// so solving for many right hand sides b only needs A^T b and small linear systems.
+/// All buffers are allocated in the constructor.
+pub struct NnlsSolver {
+    rows: usize,
+    cols: usize,
+    // column major, rows x cols
+    matrix: Vec<f64>,
+    gram: Vec<f64>,
+    atb: Vec<f64>,
+    gradient: Vec<f64>,
+    passive: Vec<bool>,
+    passive_indices: Vec<usize>,
+    z: Vec<f64>,
+    system: Vec<f64>,
+    rhs: Vec<f64>,
+}
+
+impl NnlsSolver {
+    /// `matrix` is column major with `rows` rows.
+    pub fn new(matrix: Vec<f64>, rows: usize) -> Self {
+        assert!(rows > 0 && matrix.len() % rows == 0, "matrix length must be a multiple of rows");
+        let cols = matrix.len() / rows;
+        let mut gram = vec![0.0; cols * cols];
+        for i in 0..cols {
+            for j in 0..cols {
+                gram[i * cols + j] = dot(column(&matrix, rows, i), column(&matrix, rows, j));
+            }
+        }
+
+        Self {
+            rows,
+            cols,
+            matrix,
+            gram,
+            atb: vec![0.0; cols],
+            gradient: vec![0.0; cols],
+            passive: vec![false; cols],
+            passive_indices: Vec::with_capacity(cols),
+            z: vec![0.0; cols],
+            system: vec![0.0; cols * cols],
+            rhs: vec![0.0; cols],
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Writes the solution for `b` (length rows) into `x` (length cols).
+    /// If `b` contains non finite values, x is set to 0.
+    pub fn solve(&mut self, b: &[f64], x: &mut [f64]) {
+        assert_eq!(b.len(), self.rows);
+        assert_eq!(x.len(), self.cols);
+        let cols = self.cols;
+        if !b.iter().all(|v| v.is_finite()) {
+            for x in x.iter_mut() {
+                *x = 0.0;
+            }
+            return;
+        }
+
+        for (j, atb) in self.atb.iter_mut().enumerate() {
+            *atb = dot(column(&self.matrix, self.rows, j), b);
+        }
+        for (passive, x) in self.passive.iter_mut().zip(x.iter_mut()) {
+            *passive = false;
+            *x = 0.0;
+        }
+        let tolerance = TOLERANCE * self.atb.iter().fold(1.0, |max, &v| f64::max(max, v.abs()));
+
+        for _ in 0..(3 * cols) {
+            self.update_gradient(x);
+            let next = (0..cols)
+                .filter(|&j| !self.passive[j])
+                .max_by(|&a, &b| self.gradient[a].total_cmp(&self.gradient[b]));
+            match next {
+                Some(j) if self.gradient[j] > tolerance => self.passive[j] = true,
+                _ => break,
+            }
+
+            for _ in 0..(3 * cols) {
+                self.solve_passive_system();
+                let all_positive = (0..cols).filter(|&j| self.passive[j]).all(|j| self.z[j] > 0.0);
+                if all_positive {
+                    x.copy_from_slice(&self.z);
+                    break;
+                }
+
+                // move from x towards z until the first passive variable hits zero
+                let alpha = (0..cols)
+                    .filter(|&j| self.passive[j] && self.z[j] <= 0.0)
+                    .map(|j| x[j] / (x[j] - self.z[j]))
+                    .fold(f64::INFINITY, f64::min);
+                for ((x, &z), passive) in x.iter_mut().zip(&self.z).zip(self.passive.iter_mut()) {
+                    *x += alpha * (z - *x);
+                    if *passive && *x <= TOLERANCE {
+                        *passive = false;
+                        *x = 0.0;
+                    }
+                }
+            }
+        }
+    }
+
+    fn update_gradient(&mut self, x: &[f64]) {
+        let cols = self.cols;
+        for i in 0..cols {
+            self.gradient[i] = self.atb[i] - dot(&self.gram[i * cols..(i + 1) * cols], x);
+        }
+    }
+
+    // Least squares solution restricted to the passive variables, written into z.
+    fn solve_passive_system(&mut self) {
+        let cols = self.cols;
+        let passive = &self.passive;
+        self.passive_indices.clear();
+        self.passive_indices.extend((0..cols).filter(|&j| passive[j]));
+        let n = self.passive_indices.len();
+
+        for (r, &i) in self.passive_indices.iter().enumerate() {
+            for (c, &j) in self.passive_indices.iter().enumerate() {
+                self.system[r * n + c] = self.gram[i * cols + j];
+            }
+            self.rhs[r] = self.atb[i];
+        }
+        solve_linear_system(&mut self.system[..n * n], &mut self.rhs[..n]);
+
+        for z in &mut self.z {
+            *z = 0.0;
+        }
+        for (r, &i) in self.passive_indices.iter().enumerate() {
+            self.z[i] = self.rhs[r];
+        }
+    }
+}
+
+fn column(matrix: &[f64], rows: usize, j: usize) -> &[f64] {
+    &matrix[j * rows..(j + 1) * rows]
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Gaussian elimination with partial pivoting, the solution replaces rhs.
+fn solve_linear_system(system: &mut [f64], rhs: &mut [f64]) {
+    let n = rhs.len();
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&a, &b| system[a * n + col].abs().total_cmp(&system[b * n + col].abs()))
+            .unwrap();
+        if pivot != col {
+            for c in 0..n {
+                system.swap(col * n + c, pivot * n + c);
+            }
+            rhs.swap(col, pivot);
+        }
+
+        let diagonal = system[col * n + col];
+        if diagonal.abs() < f64::EPSILON {
+            continue;
+        }
+        for row in (col + 1)..n {
+            let factor = system[row * n + col] / diagonal;
+            for c in col..n {
+                system[row * n + c] -= factor * system[col * n + c];
+            }
+            rhs[row] -= factor * rhs[col];
+        }
+    }
+
+    for row in (0..n).rev() {
+        let diagonal = system[row * n + row];
+        if diagonal.abs() < f64::EPSILON {
+            rhs[row] = 0.0;
+            continue;
+        }
+        let sum: f64 = ((row + 1)..n).map(|c| system[row * n + c] * rhs[c]).sum();
+        rhs[row] = (rhs[row] - sum) / diagonal;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_non_negative_solution() {
+        // columns (1, 0, 1), (0, 1, 1)
+        let mut solver = NnlsSolver::new(vec![1.0, 0.0, 1.0, 0.0, 1.0, 1.0], 3);
+        let mut x = vec![0.0; 2];
+        solver.solve(&[2.0, 3.0, 5.0], &mut x);
+        assert!((x[0] - 2.0).abs() < 1e-9 && (x[1] - 3.0).abs() < 1e-9, "x = {:?}", x);
+    }
+
+    #[test]
+    fn clamps_negative_coefficients() {
+        // unconstrained least squares would give x = (2, -1)
+        let mut solver = NnlsSolver::new(vec![1.0, 0.0, 1.0, 1.0], 2);
+        let mut x = vec![0.0; 2];
+        solver.solve(&[1.0, -1.0], &mut x);
+        assert!((x[0] - 1.0).abs() < 1e-9 && x[1] == 0.0, "x = {:?}", x);
+    }
+
+    #[test]
+    fn non_finite_input_gives_zero() {
+        let mut solver = NnlsSolver::new(vec![1.0, 0.0, 1.0, 0.0, 1.0, 1.0], 3);
+        let mut x = vec![1.0; 2];
+        solver.solve(&[2.0, f64::NAN, 5.0], &mut x);
+        assert_eq!(x, vec![0.0; 2]);
+        solver.solve(&[f64::INFINITY, 3.0, 5.0], &mut x);
+        assert_eq!(x, vec![0.0; 2]);
+    }
+}
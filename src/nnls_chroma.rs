@@ -0,0 +1,284 @@
+use std::error::Error;
+use std::f64;
+use std::f64::consts::PI;
+use std::fmt;
+
+use constant_q::ConstantQ;
+use nnls::NnlsSolver;
+use tuning::Tuning;
+
+const NUM_NOTES_IN_OCTAVE: usize = 12;
+/// Resolution of the log-frequency spectrum, which is interpolated from a linear magnitude spectrum
+const BINS_PER_SEMITONE: usize = 3;
+
+/// Approximate transcription chroma (Mauch and Dixon, 2010).
+#[derive(Clone, Debug, PartialEq)]
+pub struct NnlsChromaInitProps {
+    /// Number of harmonics in every note template
+    pub num_harmonics: usize,
+    /// Amplitude of harmonic h in the note templates is harmonic_decay^(h-1)
+    pub harmonic_decay: f64,
+    /// Range of MIDI notes [start, end) folded into the bass chroma, weighted by a raised cosine
+    pub bass_range: (usize, usize),
+    /// Range of MIDI notes [start, end) folded into the treble chroma, weighted by a raised cosine
+    pub treble_range: (usize, usize),
+    pub tuning: Tuning,
+}
+
+impl NnlsChromaInitProps {
+    /// Lowest and highest (inclusive) MIDI note of the log-frequency spectrum interpolated by
+    /// `NnlsChroma::for_linear_spectrum`, with a semitone below the ranges and an octave above them
+    /// for the second harmonics.
+    pub fn linear_spectrum_note_range(&self) -> (usize, usize) {
+        let start = self.bass_range.0.min(self.treble_range.0).saturating_sub(1);
+        let end = self.bass_range.1.max(self.treble_range.1) + NUM_NOTES_IN_OCTAVE;
+        (start, end.max(start))
+    }
+}
+
+impl Default for NnlsChromaInitProps {
+    fn default() -> Self {
+        Self {
+            num_harmonics: 8,
+            harmonic_decay: 0.7,
+            bass_range: (28, 60),   // E1 - C4
+            treble_range: (48, 96), // C3 - C7
+            tuning: Tuning::default(),
+        }
+    }
+}
+
+/// Decomposes a log-frequency spectrum into note activations by non-negative least squares
+/// with harmonic note templates, and folds the activations into treble and bass chroma.
+///
+/// Because overtones are explained by the note templates, they don't show up as extra pitch classes.
+pub struct NnlsChroma {
+    props: NnlsChromaInitProps,
+    min_note: usize,
+    solver: NnlsSolver,
+    frequencies: Vec<f64>,
+    log_spectrum: Vec<f64>,
+    /// Activation of every note, starting at `min_note()`
+    pub note_activations: Vec<f64>,
+    /// Chroma of the treble range, index 0 = C
+    pub treble_chroma: Vec<f64>,
+    /// Chroma of the bass range, index 0 = C
+    pub bass_chroma: Vec<f64>,
+}
+
+impl NnlsChroma {
+    /// Creates note templates for the bins of a log-frequency spectrum with the given center frequencies.
+    /// One note template is created for every semitone in the frequency range.
+    /// The frequencies must be positive and ascending.
+    pub fn new(props: NnlsChromaInitProps, frequencies: &[f64]) -> Result<Self, NnlsChromaError> {
+        if frequencies.is_empty() {
+            return Err(NnlsChromaError::InvalidFrequencies("frequencies must not be empty"));
+        }
+        if !frequencies.iter().all(|f| f.is_finite() && *f > 0.0) {
+            return Err(NnlsChromaError::InvalidFrequencies("frequencies must be positive"));
+        }
+        if !frequencies.windows(2).all(|pair| pair[0] < pair[1]) {
+            return Err(NnlsChromaError::InvalidFrequencies("frequencies must be ascending"));
+        }
+        let midi_note = |frequency: f64| 69.0 + 12.0 * (frequency / props.tuning.reference_frequency()).log2();
+        let min_note = midi_note(frequencies[0]).round().max(0.0) as usize;
+        let max_note = (midi_note(frequencies[frequencies.len() - 1]).round().max(0.0) as usize).max(min_note);
+        let num_notes = max_note + 1 - min_note;
+
+        let rows = frequencies.len();
+        let mut dictionary = vec![0.0; rows * num_notes];
+        for (i, template) in dictionary.chunks_mut(rows).enumerate() {
+            let note_frequency = props.tuning.note_frequency((min_note + i) as f64);
+            for harmonic in 1..(props.num_harmonics + 1) {
+                let amplitude = props.harmonic_decay.powi(harmonic as i32 - 1);
+                for (value, &frequency) in template.iter_mut().zip(frequencies) {
+                    // distance in semitones, the partial is spread over +-0.5 semitones
+                    let distance = 12.0 * (frequency / (note_frequency * harmonic as f64)).log2();
+                    if distance.abs() < 0.5 {
+                        *value += amplitude * (PI * distance).cos().powi(2);
+                    }
+                }
+            }
+            let norm = template.iter().map(|v| v * v).sum::<f64>().sqrt();
+            if norm > 0.0 {
+                for value in template.iter_mut() {
+                    *value /= norm;
+                }
+            }
+        }
+
+        Ok(Self {
+            props,
+            min_note,
+            solver: NnlsSolver::new(dictionary, rows),
+            frequencies: frequencies.to_vec(),
+            log_spectrum: vec![0.0; rows],
+            note_activations: vec![0.0; num_notes],
+            treble_chroma: vec![0.0; NUM_NOTES_IN_OCTAVE],
+            bass_chroma: vec![0.0; NUM_NOTES_IN_OCTAVE],
+        })
+    }
+
+    /// Creates note templates for the bins of a constant-Q transform.
+    pub fn from_constant_q(props: NnlsChromaInitProps, constant_q: &ConstantQ) -> Result<Self, NnlsChromaError> {
+        Self::new(props, constant_q.frequencies())
+    }
+
+    /// Creates note templates for a log-frequency spectrum with 3 bins per semitone over
+    /// `linear_spectrum_note_range`, which `process_magnitude_spectrum` interpolates from a linear spectrum.
+    pub fn for_linear_spectrum(props: NnlsChromaInitProps) -> Self {
+        let (start, end) = props.linear_spectrum_note_range();
+        let frequencies: Vec<f64> = (start * BINS_PER_SEMITONE..(end * BINS_PER_SEMITONE + 1))
+            .map(|i| props.tuning.note_frequency(i as f64 / BINS_PER_SEMITONE as f64))
+            .collect();
+        Self::new(props, &frequencies).expect("note frequencies are positive and ascending")
+    }
+
+    /// MIDI note of the first note activation
+    pub fn min_note(&self) -> usize {
+        self.min_note
+    }
+
+    /// Interpolates the log-frequency spectrum from a linear magnitude spectrum (bins 0 to N/2)
+    /// and processes it. The note templates are moved to the `tuning`, e.g. an estimated one.
+    pub fn process_magnitude_spectrum(&mut self, magnitude_spectrum: &[f64], bin_frequency: f64, tuning: Tuning) {
+        let tuning_ratio = tuning.reference_frequency() / self.props.tuning.reference_frequency();
+        for (value, &frequency) in self.log_spectrum.iter_mut().zip(&self.frequencies) {
+            let position = frequency * tuning_ratio / bin_frequency;
+            let bin = position.floor() as usize;
+            let fraction = position - bin as f64;
+            *value = match (magnitude_spectrum.get(bin), magnitude_spectrum.get(bin + 1)) {
+                (Some(&a), Some(&b)) => a + fraction * (b - a),
+                (Some(&a), None) => a,
+                _ => 0.0,
+            };
+        }
+        let log_spectrum = ::std::mem::take(&mut self.log_spectrum);
+        self.process_log_spectrum(&log_spectrum);
+        self.log_spectrum = log_spectrum;
+    }
+
+    /// Processes a log-frequency magnitude spectrum with the bins given in the constructor.
+    /// Non finite values result in zero activations and chroma.
+    pub fn process_log_spectrum(&mut self, spectrum: &[f64]) {
+        self.solver.solve(spectrum, &mut self.note_activations);
+
+        for x in self.treble_chroma.iter_mut().chain(self.bass_chroma.iter_mut()) {
+            *x = 0.0;
+        }
+        for (i, &activation) in self.note_activations.iter().enumerate() {
+            let note = self.min_note + i;
+            let pitch_class = note % NUM_NOTES_IN_OCTAVE;
+            self.bass_chroma[pitch_class] += range_weight(note, self.props.bass_range) * activation;
+            self.treble_chroma[pitch_class] += range_weight(note, self.props.treble_range) * activation;
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum NnlsChromaError {
+    InvalidFrequencies(&'static str),
+}
+
+impl fmt::Display for NnlsChromaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NnlsChromaError::InvalidFrequencies(reason) => write!(f, "Invalid NNLS chroma frequencies: {}", reason),
+        }
+    }
+}
+
+impl Error for NnlsChromaError {}
+
+fn range_weight(note: usize, (start, end): (usize, usize)) -> f64 {
+    if note < start || note >= end {
+        return 0.0;
+    }
+    (PI * (note - start) as f64 / (end - start) as f64).sin().powi(2)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use constant_q::ConstantQInitProps;
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 11_025.0;
+
+    // Tones with decaying harmonics
+    fn harmonic_tones(notes: &[f64], length: usize) -> Vec<f64> {
+        let tuning = Tuning::default();
+        (0..length)
+            .map(|i| notes.iter()
+                .flat_map(|&note| (1..6).map(move |h| (note, h)))
+                .map(|(note, h)| {
+                    let frequency = tuning.note_frequency(note) * h as f64;
+                    0.8f64.powi(h - 1) * (2.0 * PI * frequency * i as f64 / SAMPLE_RATE).sin()
+                })
+                .sum())
+            .collect()
+    }
+
+    fn constant_q() -> ConstantQ {
+        ConstantQ::new(ConstantQInitProps { sample_rate: SAMPLE_RATE as usize, num_octaves: 4, ..Default::default() })
+    }
+
+    #[test]
+    fn suppresses_overtones() {
+        let mut constant_q = constant_q();
+        let fft_size = constant_q.fft_size();
+        // C3, its third harmonic is a G
+        constant_q.process_audio_frame(&harmonic_tones(&[48.0], fft_size));
+
+        let mut folded_chroma = vec![0.0; 12];
+        constant_q.fold_to_chroma(Tuning::default(), &mut folded_chroma);
+        assert!(folded_chroma[7] > 0.2 * folded_chroma[0], "folded chroma = {:?}", folded_chroma);
+
+        let mut nnls_chroma = NnlsChroma::from_constant_q(NnlsChromaInitProps::default(), &constant_q).unwrap();
+        nnls_chroma.process_log_spectrum(&constant_q.spectrum);
+        let chroma = &nnls_chroma.treble_chroma;
+        assert!(chroma[7] < 0.1 * chroma[0], "nnls chroma = {:?}", chroma);
+    }
+
+    #[test]
+    fn separates_bass_and_treble() {
+        let mut constant_q = constant_q();
+        let fft_size = constant_q.fft_size();
+        // C major with E in the bass: E2, C4, E4, G4
+        constant_q.process_audio_frame(&harmonic_tones(&[40.0, 60.0, 64.0, 67.0], fft_size));
+
+        let mut nnls_chroma = NnlsChroma::from_constant_q(NnlsChromaInitProps::default(), &constant_q).unwrap();
+        nnls_chroma.process_log_spectrum(&constant_q.spectrum);
+
+        let strongest = |chroma: &[f64]| (0..12).max_by(|&a, &b| chroma[a].partial_cmp(&chroma[b]).unwrap()).unwrap();
+        assert_eq!(strongest(&nnls_chroma.bass_chroma), 4);
+
+        let treble = &nnls_chroma.treble_chroma;
+        let mut strongest_treble: Vec<usize> = (0..12).collect();
+        strongest_treble.sort_by(|&a, &b| treble[b].partial_cmp(&treble[a]).unwrap());
+        strongest_treble.truncate(3);
+        strongest_treble.sort();
+        assert_eq!(strongest_treble, vec![0, 4, 7]);
+    }
+
+    #[test]
+    fn rejects_invalid_frequencies() {
+        let props = NnlsChromaInitProps::default;
+        assert_eq!(NnlsChroma::new(props(), &[]).err(), Some(NnlsChromaError::InvalidFrequencies("frequencies must not be empty")));
+        assert!(NnlsChroma::new(props(), &[110.0, f64::NAN]).is_err());
+        assert!(NnlsChroma::new(props(), &[0.0, 110.0]).is_err());
+        assert!(NnlsChroma::new(props(), &[220.0, 110.0]).is_err());
+        assert!(NnlsChroma::new(props(), &[110.0]).is_ok());
+    }
+
+    #[test]
+    fn non_finite_spectrum_gives_zero_chroma() {
+        let mut constant_q = constant_q();
+        let fft_size = constant_q.fft_size();
+        constant_q.process_audio_frame(&harmonic_tones(&[48.0], fft_size));
+        let mut nnls_chroma = NnlsChroma::from_constant_q(NnlsChromaInitProps::default(), &constant_q).unwrap();
+        constant_q.spectrum[3] = f64::NAN;
+        nnls_chroma.process_log_spectrum(&constant_q.spectrum);
+        assert_eq!(nnls_chroma.treble_chroma, vec![0.0; 12]);
+    }
+}
@@ -0,0 +1,58 @@
+use std::f64;
+
+/// Normalization of feature vectors like chroma.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Normalization {
+    None,
+    /// Sum of the absolute values is 1
+    L1,
+    /// Euclidean length is 1
+    L2,
+    /// Largest absolute value is 1
+    Max,
+}
+
+impl Normalization {
+    /// Normalizes the values in place. Vectors with a norm of 0 (e.g. of silence) are left unchanged,
+    /// so that no NaNs are produced.
+    pub fn apply(self, values: &mut [f64]) {
+        let norm = match self {
+            Normalization::None => return,
+            Normalization::L1 => values.iter().map(|x| x.abs()).sum(),
+            Normalization::L2 => values.iter().map(|x| x * x).sum::<f64>().sqrt(),
+            Normalization::Max => values.iter().fold(0.0, |max, x| f64::max(max, x.abs())),
+        };
+        if norm > 0.0 && norm.is_finite() {
+            for x in values.iter_mut() {
+                *x /= norm;
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_without_nans() {
+        let mut values = [3.0, -4.0];
+        Normalization::L1.apply(&mut values);
+        assert_eq!(values, [3.0 / 7.0, -4.0 / 7.0]);
+
+        let mut values = [3.0, -4.0];
+        Normalization::L2.apply(&mut values);
+        assert_eq!(values, [0.6, -0.8]);
+
+        let mut values = [3.0, -4.0];
+        Normalization::Max.apply(&mut values);
+        assert_eq!(values, [0.75, -1.0]);
+
+        let mut silence = [0.0; 12];
+        for &normalization in &[Normalization::None, Normalization::L1, Normalization::L2, Normalization::Max] {
+            normalization.apply(&mut silence);
+            assert_eq!(silence, [0.0; 12]);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+/// An audio sample, which can be converted from and to f64 at full scale [-1, 1].
+///
+/// All feature extractors accept any sample type and work internally on f64,
+/// so float audio is never quantised.
+pub trait Sample: Copy {
+    fn to_f64(self) -> f64;
+
+    /// Clamps values outside of [-1, 1].
+    fn from_f64(value: f64) -> Self;
+}
+
+impl Sample for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value.clamp(-1.0, 1.0)
+    }
+}
+
+impl Sample for f32 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value.clamp(-1.0, 1.0) as f32
+    }
+}
+
+impl Sample for i16 {
+    fn to_f64(self) -> f64 {
+        self as f64 / -(i16::MIN as f64)
+    }
+
+    fn from_f64(value: f64) -> Self {
+        (value * -(i16::MIN as f64)).round().clamp(i16::MIN as f64, i16::MAX as f64) as i16
+    }
+}
+
+impl Sample for i32 {
+    fn to_f64(self) -> f64 {
+        self as f64 / -(i32::MIN as f64)
+    }
+
+    fn from_f64(value: f64) -> Self {
+        (value * -(i32::MIN as f64)).round().clamp(i32::MIN as f64, i32::MAX as f64) as i32
+    }
+}
+
+/// Unsigned 8 bit samples are centered around 128, like in 8 bit WAV files.
+impl Sample for u8 {
+    fn to_f64(self) -> f64 {
+        (self as f64 - 128.0) / 128.0
+    }
+
+    fn from_f64(value: f64) -> Self {
+        (value * 128.0 + 128.0).round().clamp(0.0, 255.0) as u8
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_scale_conversions() {
+        assert_eq!(i16::MIN.to_f64(), -1.0);
+        assert_eq!(16_384i16.to_f64(), 0.5);
+        assert_eq!(i32::MIN.to_f64(), -1.0);
+        assert_eq!(128u8.to_f64(), 0.0);
+        assert_eq!(0u8.to_f64(), -1.0);
+        assert_eq!(0.25f32.to_f64(), 0.25);
+
+        assert_eq!(i16::from_f64(0.5), 16_384);
+        assert_eq!(i16::from_f64(2.0), i16::MAX);
+        assert_eq!(u8::from_f64(-1.0), 0);
+        assert_eq!(u8::from_f64(1.0), 255);
+        assert_eq!(f64::from_f64(-3.0), -1.0);
+
+        for &x in &[-32_768i16, -1, 0, 1, 12_345, 32_767] {
+            assert_eq!(i16::from_f64(x.to_f64()), x);
+        }
+    }
+}
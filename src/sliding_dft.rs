@@ -0,0 +1,83 @@
+use std::f64;
+use std::f64::consts::PI;
+
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+
+// the phasor is renormalized after this number of samples to avoid drift
+const RENORMALIZATION_INTERVAL: usize = 1024;
+
+/// DFT of a single frequency over the last `window_size` samples, updated recursively per sample.
+///
+/// Unlike a Goertzel filter over the whole window, every new sample costs only a few multiplications,
+/// independent of the window size. The frequency doesn't need to be a multiple of sample_rate / window_size.
+/// The window is always rectangular.
+#[derive(Clone, Debug)]
+pub struct SlidingDft {
+    // e^(-jω), rotates the phasor by one sample
+    rotation: Complex<f64>,
+    // e^(jωN), phase of the sample leaving the window relative to the current phasor
+    delay_rotation: Complex<f64>,
+    // e^(-jωn) of the current sample n
+    phasor: Complex<f64>,
+    sum: Complex<f64>,
+    num_samples: usize,
+}
+
+impl SlidingDft {
+    pub fn new(frequency: f64, sample_rate: f64, window_size: usize) -> Self {
+        let omega = 2.0 * PI * frequency / sample_rate;
+        Self {
+            rotation: Complex::from_polar(&1.0, &-omega),
+            delay_rotation: Complex::from_polar(&1.0, &(omega * window_size as f64)),
+            phasor: Complex::new(1.0, 0.0),
+            sum: Complex::zero(),
+            num_samples: 0,
+        }
+    }
+
+    /// Adds a new sample and removes the sample, which was added `window_size` samples before (0 at the start).
+    pub fn update(&mut self, new_sample: f64, old_sample: f64) {
+        self.sum += self.phasor * (new_sample - old_sample * self.delay_rotation);
+        self.phasor *= self.rotation;
+        self.num_samples += 1;
+        if self.num_samples % RENORMALIZATION_INTERVAL == 0 {
+            self.phasor = self.phasor / self.phasor.norm();
+        }
+    }
+
+    pub fn magnitude(&self) -> f64 {
+        self.sum.norm()
+    }
+
+    pub fn reset(&mut self) {
+        self.phasor = Complex::new(1.0, 0.0);
+        self.sum = Complex::zero();
+        self.num_samples = 0;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equals_dft_of_last_window() {
+        let (sample_rate, window_size, frequency) = (8000.0, 200, 441.3);
+        let signal: Vec<f64> = (0..5000)
+            .map(|i| (2.0 * PI * 440.0 * i as f64 / sample_rate).sin() + 0.3 * (i as f64 * 0.37).cos())
+            .collect();
+
+        let mut sliding_dft = SlidingDft::new(frequency, sample_rate, window_size);
+        for (i, &x) in signal.iter().enumerate() {
+            let old = if i >= window_size { signal[i - window_size] } else { 0.0 };
+            sliding_dft.update(x, old);
+        }
+
+        let dft = signal[signal.len() - window_size..].iter().enumerate()
+            .map(|(m, &x)| Complex::from_polar(&x, &(-2.0 * PI * frequency * m as f64 / sample_rate)))
+            .fold(Complex::zero(), |sum: Complex<f64>, x| sum + x);
+        assert!((sliding_dft.magnitude() - dft.norm()).abs() < 1e-6, "{} != {}", sliding_dft.magnitude(), dft.norm());
+    }
+}
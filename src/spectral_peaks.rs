@@ -0,0 +1,65 @@
+use std::f64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpectralPeak {
+    pub frequency: f64,
+    pub magnitude: f64,
+}
+
+/// Local maxima of a magnitude spectrum (bins 0 to N/2) between min_frequency and max_frequency,
+/// which are at least threshold * the largest magnitude in this range.
+///
+/// Frequency and magnitude of every peak are refined by parabolic interpolation of the log magnitudes.
+/// `bin_frequency` is the distance of two bins in Hz, i.e. sample_rate / fft_size.
+pub fn spectral_peaks<'a>(magnitude_spectrum: &'a [f64], bin_frequency: f64, min_frequency: f64, max_frequency: f64,
+                          threshold: f64) -> impl Iterator<Item = SpectralPeak> + 'a {
+    let min_bin = ((min_frequency / bin_frequency).floor() as usize).max(1);
+    let max_bin = ((max_frequency / bin_frequency).ceil() as usize).min(magnitude_spectrum.len().saturating_sub(1));
+    let max_bin = max_bin.max(min_bin);
+
+    let max_magnitude = magnitude_spectrum.get(min_bin..max_bin).unwrap_or(&[]).iter().cloned().fold(0.0, f64::max);
+    let min_magnitude = f64::max(max_magnitude * threshold, f64::MIN_POSITIVE);
+
+    (min_bin..max_bin)
+        .filter(move |&k| {
+            let magnitude = magnitude_spectrum[k];
+            magnitude >= min_magnitude && magnitude > magnitude_spectrum[k - 1] && magnitude >= magnitude_spectrum[k + 1]
+        })
+        .map(move |k| {
+            let (offset, magnitude) = interpolate_peak(magnitude_spectrum[k - 1], magnitude_spectrum[k], magnitude_spectrum[k + 1]);
+            SpectralPeak { frequency: (k as f64 + offset) * bin_frequency, magnitude }
+        })
+}
+
+/// Offset in bins of the true peak from the middle bin and its magnitude,
+/// interpolated by a parabola through the log magnitudes.
+fn interpolate_peak(left: f64, middle: f64, right: f64) -> (f64, f64) {
+    let (l, m, r) = (left.max(f64::MIN_POSITIVE).ln(), middle.ln(), right.max(f64::MIN_POSITIVE).ln());
+    let denominator = l - 2.0 * m + r;
+    if denominator == 0.0 {
+        return (0.0, middle);
+    }
+    let offset = (0.5 * (l - r) / denominator).clamp(-0.5, 0.5);
+    (offset, (m - 0.25 * (l - r) * offset).exp())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use spectrum_analyzer::SpectrumAnalyzer;
+    use window::WindowFunction;
+    use super::*;
+
+    #[test]
+    fn interpolates_peak_frequency() {
+        let sample_rate = 44_100.0;
+        let fft_size = 4096;
+        let mut analyzer = SpectrumAnalyzer::with_window_function(fft_size, WindowFunction::Hann);
+        let spectrum = analyzer.magnitude_spectrum((0..fft_size).map(|i| (2.0 * PI * 445.0 * i as f64 / sample_rate).sin()));
+
+        let peaks: Vec<SpectralPeak> = spectral_peaks(spectrum, sample_rate / fft_size as f64, 50.0, 5000.0, 0.1).collect();
+        assert_eq!(peaks.len(), 1);
+        assert!((peaks[0].frequency - 445.0).abs() < 0.5, "peaks = {:?}", peaks);
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use rustfft::{FFT, FFTplanner};
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+
+use sample::Sample;
+use window::WindowFunction;
+
+/// Calculates spectra of a fixed size.
+///
+/// The FFT plan and all buffers are created once in the constructor,
+/// so processing does not allocate and is safe to call from an audio thread.
+pub struct SpectrumAnalyzer {
+    fft: Arc<dyn FFT<f64>>,
+    window: Vec<f64>,
+    fft_in: Vec<Complex<f64>>,
+    fft_out: Vec<Complex<f64>>,
+    spectrum: Vec<f64>,
+}
+
+impl SpectrumAnalyzer {
+    /// Creates an analyzer without windowing (rectangular window).
+    pub fn new(size: usize) -> Self {
+        Self::with_window(vec![1.0; size])
+    }
+
+    pub fn with_window_function(size: usize, window: WindowFunction) -> Self {
+        Self::with_window(window.generate(size))
+    }
+
+    /// Creates an analyzer, which multiplies the samples with the window. The size is the window length.
+    pub fn with_window(window: Vec<f64>) -> Self {
+        let size = window.len();
+        let mut planner = FFTplanner::new(false);
+        Self {
+            fft: planner.plan_fft(size),
+            window,
+            fft_in: vec![Complex::zero(); size],
+            fft_out: vec![Complex::zero(); size],
+            spectrum: vec![0.0; size],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.window.len()
+    }
+
+    /// Calculates the FFT of the windowed samples. Missing samples are zero padded, extra samples are ignored.
+    pub fn process<S: Sample, I>(&mut self, samples: I) -> &[Complex<f64>] where I: IntoIterator<Item = S> {
+        let mut samples = samples.into_iter();
+        for (fft_in, &w) in self.fft_in.iter_mut().zip(&self.window) {
+            *fft_in = Complex::new(samples.next().map_or(0.0, Sample::to_f64) * w, 0.0);
+        }
+        self.fft.process(&mut self.fft_in, &mut self.fft_out);
+        &self.fft_out
+    }
+
+    /// Squared magnitudes of all `size` bins.
+    pub fn power_spectrum<S: Sample, I>(&mut self, samples: I) -> &[f64] where I: IntoIterator<Item = S> {
+        self.process(samples);
+        for (s, c) in self.spectrum.iter_mut().zip(&self.fft_out) {
+            *s = c.norm_sqr();
+        }
+        &self.spectrum
+    }
+
+    /// Magnitudes of the bins 0 to size / 2.
+    pub fn magnitude_spectrum<S: Sample, I>(&mut self, samples: I) -> &[f64] where I: IntoIterator<Item = S> {
+        self.process(samples);
+        let len = self.size() / 2 + 1;
+        for (s, c) in self.spectrum[..len].iter_mut().zip(&self.fft_out) {
+            *s = c.norm();
+        }
+        &self.spectrum[..len]
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use super::*;
+
+    #[test]
+    fn magnitude_of_sine() {
+        let mut analyzer = SpectrumAnalyzer::new(64);
+        let sine = (0..64).map(|i| (2.0 * PI * 4.0 * i as f64 / 64.0).sin());
+        let spectrum = analyzer.magnitude_spectrum(sine).to_vec();
+
+        assert_eq!(spectrum.len(), 33);
+        assert!((spectrum[4] - 32.0).abs() < 1e-9);
+        assert!(spectrum.iter().enumerate().filter(|&(k, _)| k != 4).all(|(_, &m)| m < 1e-9));
+    }
+
+    #[test]
+    fn zero_pads_short_input() {
+        let mut analyzer = SpectrumAnalyzer::new(8);
+        let power = analyzer.power_spectrum(vec![1.0]);
+        assert_eq!(power, &[1.0; 8]);
+    }
+}
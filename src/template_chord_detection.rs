@@ -0,0 +1,417 @@
+//=======================================================================
+/*  @file template_chord_detection.rs
+ *  @brief TemplateChordDetector - chord recognition from a 12 bin chromagram
+ *  @rust-implementation Marco Stahl
+ *  @copyright Copyright (C) 2018 Marco Stahl
+ *
+ *  Based on https://github.com/adamstark/Chord-Detector-and-Chromagram
+ *  @original-file ChordDetector.cpp
+ *  @author Adam Stark
+ *  @copyright Copyright (C) 2008-2014  Queen Mary University of London
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * Like chromagram.rs, this file is a port of GPL licensed code and stays under the GPL,
+ * while the rest of the crate is MIT licensed.
+ */
+//=======================================================================
+
+use std::f64;
+use std::fmt;
+
+use chord::Chord;
+
+const NUM_PITCH_CLASSES: usize = 12;
+
+pub const PITCH_CLASS_NAMES: [&str; NUM_PITCH_CLASSES] =
+    ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChordQuality {
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Sus2,
+    Sus4,
+    Major7,
+    Minor7,
+    Dominant7,
+    Diminished7,
+    HalfDiminished7,
+    MinorMajor7,
+    Major6,
+    Minor6,
+    /// Root and fifth without third
+    Power,
+}
+
+impl ChordQuality {
+    pub const ALL: [ChordQuality; 15] = [
+        ChordQuality::Major,
+        ChordQuality::Minor,
+        ChordQuality::Diminished,
+        ChordQuality::Augmented,
+        ChordQuality::Sus2,
+        ChordQuality::Sus4,
+        ChordQuality::Major7,
+        ChordQuality::Minor7,
+        ChordQuality::Dominant7,
+        ChordQuality::Diminished7,
+        ChordQuality::HalfDiminished7,
+        ChordQuality::MinorMajor7,
+        ChordQuality::Major6,
+        ChordQuality::Minor6,
+        ChordQuality::Power,
+    ];
+
+    /// The qualities, for which the `TemplateChordDetector` has templates.
+    pub const DETECTED: [ChordQuality; 9] = [
+        ChordQuality::Major,
+        ChordQuality::Minor,
+        ChordQuality::Diminished,
+        ChordQuality::Augmented,
+        ChordQuality::Sus2,
+        ChordQuality::Sus4,
+        ChordQuality::Major7,
+        ChordQuality::Minor7,
+        ChordQuality::Dominant7,
+    ];
+
+    /// Semitone intervals of the chord tones above the root.
+    pub fn intervals(self) -> &'static [usize] {
+        match self {
+            ChordQuality::Major => &[0, 4, 7],
+            ChordQuality::Minor => &[0, 3, 7],
+            ChordQuality::Diminished => &[0, 3, 6],
+            ChordQuality::Augmented => &[0, 4, 8],
+            ChordQuality::Sus2 => &[0, 2, 7],
+            ChordQuality::Sus4 => &[0, 5, 7],
+            ChordQuality::Major7 => &[0, 4, 7, 11],
+            ChordQuality::Minor7 => &[0, 3, 7, 10],
+            ChordQuality::Dominant7 => &[0, 4, 7, 10],
+            ChordQuality::Diminished7 => &[0, 3, 6, 9],
+            ChordQuality::HalfDiminished7 => &[0, 3, 6, 10],
+            ChordQuality::MinorMajor7 => &[0, 3, 7, 11],
+            ChordQuality::Major6 => &[0, 4, 7, 9],
+            ChordQuality::Minor6 => &[0, 3, 7, 9],
+            ChordQuality::Power => &[0, 7],
+        }
+    }
+
+    /// The original algorithm favours these chord types by dividing their score by the bias.
+    fn is_biased(self) -> bool {
+        !matches!(self, ChordQuality::Sus2 | ChordQuality::Sus4 | ChordQuality::Major7)
+    }
+
+    /// Lead sheet suffix, e.g. "m7" in "Am7".
+    pub fn suffix(self) -> &'static str {
+        match self {
+            ChordQuality::Major => "",
+            ChordQuality::Minor => "m",
+            ChordQuality::Diminished => "dim",
+            ChordQuality::Augmented => "aug",
+            ChordQuality::Sus2 => "sus2",
+            ChordQuality::Sus4 => "sus4",
+            ChordQuality::Major7 => "maj7",
+            ChordQuality::Minor7 => "m7",
+            ChordQuality::Dominant7 => "7",
+            ChordQuality::Diminished7 => "dim7",
+            ChordQuality::HalfDiminished7 => "m7b5",
+            ChordQuality::MinorMajor7 => "mmaj7",
+            ChordQuality::Major6 => "6",
+            ChordQuality::Minor6 => "m6",
+            ChordQuality::Power => "5",
+        }
+    }
+
+    /// Shorthand in the chord syntax of Harte et al., e.g. "min7" in "A:min7".
+    pub fn harte_shorthand(self) -> &'static str {
+        match self {
+            ChordQuality::Major => "maj",
+            ChordQuality::Minor => "min",
+            ChordQuality::Diminished => "dim",
+            ChordQuality::Augmented => "aug",
+            ChordQuality::Sus2 => "sus2",
+            ChordQuality::Sus4 => "sus4",
+            ChordQuality::Major7 => "maj7",
+            ChordQuality::Minor7 => "min7",
+            ChordQuality::Dominant7 => "7",
+            ChordQuality::Diminished7 => "dim7",
+            ChordQuality::HalfDiminished7 => "hdim7",
+            ChordQuality::MinorMajor7 => "minmaj7",
+            ChordQuality::Major6 => "maj6",
+            ChordQuality::Minor6 => "min6",
+            ChordQuality::Power => "5",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChordRecognition {
+    /// Pitch class of the root, 0 = C.
+    pub root: usize,
+    pub quality: ChordQuality,
+    /// Distance between chromagram and chord template, lower is better.
+    pub score: f64,
+    /// Pitch class of the bass note, equal to the root if the chord is in root position.
+    pub bass: usize,
+}
+
+impl ChordRecognition {
+    pub fn chord(&self) -> Chord {
+        Chord::new(self.root, self.quality).with_bass(self.bass)
+    }
+}
+
+impl fmt::Display for ChordRecognition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", PITCH_CLASS_NAMES[self.root], self.quality.suffix())?;
+        if self.bass != self.root {
+            write!(f, "/{}", PITCH_CLASS_NAMES[self.bass])?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TemplateChordDetectorInitProps {
+    pub bias: f64,
+    /// Number of harmonics of each chord tone which are added to the template, 1 = plain binary templates.
+    pub num_harmonics: usize,
+    /// Weight of harmonic h is harmonic_decay^(h-1).
+    pub harmonic_decay: f64,
+    /// No chord is detected in a chroma whose sum is at most this threshold, e.g. of silence.
+    pub no_chord_threshold: f64,
+}
+
+impl Default for TemplateChordDetectorInitProps {
+    fn default() -> Self {
+        Self { bias: 1.06, num_harmonics: 1, harmonic_decay: 0.6, no_chord_threshold: 0.0 }
+    }
+}
+
+struct ChordTemplate {
+    root: usize,
+    quality: ChordQuality,
+    profile: [f64; NUM_PITCH_CLASSES],
+    // Sum of (1 - profile), which is 12 - number of chord tones for binary templates.
+    weight: f64,
+}
+
+/// Detects chords by comparing a 12 bin chromagram (index 0 = C) against chord templates.
+/// No training data is needed.
+pub struct TemplateChordDetector {
+    props: TemplateChordDetectorInitProps,
+    templates: Vec<ChordTemplate>,
+    chromagram: [f64; NUM_PITCH_CLASSES],
+}
+
+impl TemplateChordDetector {
+    pub fn new(props: TemplateChordDetectorInitProps) -> Self {
+        let templates = ChordQuality::DETECTED.iter()
+            .flat_map(|&quality| (0..NUM_PITCH_CLASSES).map(move |root| (root, quality)))
+            .map(|(root, quality)| make_chord_template(root, quality, &props))
+            .collect();
+
+        Self {
+            props,
+            templates,
+            chromagram: [0.0; NUM_PITCH_CLASSES],
+        }
+    }
+
+    /// Returns None ("no chord") if the chroma is below the no chord threshold or not finite.
+    pub fn detect(&mut self, chroma: &[f64]) -> Option<ChordRecognition> {
+        assert_eq!(chroma.len(), NUM_PITCH_CLASSES, "chroma must have 12 bins");
+        let energy: f64 = chroma.iter().map(|x| x.abs()).sum();
+        if !(energy > self.props.no_chord_threshold && energy.is_finite()) {
+            return None;
+        }
+        self.chromagram.copy_from_slice(chroma);
+
+        // remove some of the 5th note energy from chromagram
+        for i in 0..NUM_PITCH_CLASSES {
+            let fifth = (i + 7) % NUM_PITCH_CLASSES;
+            self.chromagram[fifth] = f64::max(self.chromagram[fifth] - 0.1 * self.chromagram[i], 0.0);
+        }
+
+        let mut best = ChordRecognition { root: 0, quality: ChordQuality::Major, score: f64::INFINITY, bass: 0 };
+        for template in &self.templates {
+            let score = self.calculate_chord_score(template);
+            if score < best.score {
+                best = ChordRecognition { root: template.root, quality: template.quality, score, bass: template.root };
+            }
+        }
+        Some(best)
+    }
+
+    /// Detects the chord in the treble chroma and takes the strongest pitch class of the bass chroma
+    /// as bass note, so that inversions and slash chords like C/E are recognized.
+    /// An all zero bass chroma results in a chord in root position, a non finite one in no chord.
+    pub fn detect_with_bass(&mut self, chroma: &[f64], bass_chroma: &[f64]) -> Option<ChordRecognition> {
+        assert_eq!(bass_chroma.len(), NUM_PITCH_CLASSES, "bass chroma must have 12 bins");
+        if !bass_chroma.iter().all(|x| x.is_finite()) {
+            return None;
+        }
+        let mut chord = self.detect(chroma)?;
+        let strongest_bass = (0..NUM_PITCH_CLASSES)
+            .max_by(|&a, &b| bass_chroma[a].partial_cmp(&bass_chroma[b]).unwrap())
+            .unwrap();
+        if bass_chroma[strongest_bass] > bass_chroma[chord.root] {
+            chord.bass = strongest_bass;
+        }
+        Some(chord)
+    }
+
+    fn calculate_chord_score(&self, template: &ChordTemplate) -> f64 {
+        let sum: f64 = self.chromagram.iter().zip(template.profile.iter())
+            .map(|(&c, &p)| (1.0 - p) * c * c)
+            .sum();
+        let bias = if template.quality.is_biased() { self.props.bias } else { 1.0 };
+        sum.sqrt() / (template.weight * bias)
+    }
+}
+
+fn make_chord_template(root: usize, quality: ChordQuality, props: &TemplateChordDetectorInitProps) -> ChordTemplate {
+    let mut profile = [0.0; NUM_PITCH_CLASSES];
+    for &interval in quality.intervals() {
+        for harmonic in 1..(props.num_harmonics.max(1) + 1) {
+            let semitones = (12.0 * (harmonic as f64).log2()).round() as usize;
+            let pitch_class = (root + interval + semitones) % NUM_PITCH_CLASSES;
+            profile[pitch_class] += props.harmonic_decay.powi(harmonic as i32 - 1);
+        }
+    }
+
+    // chord tones are fully expected, harmonics falling on other pitch classes only partially
+    for p in &mut profile {
+        *p = p.min(1.0);
+    }
+
+    let weight = profile.iter().map(|p| 1.0 - p).sum();
+    ChordTemplate { root, quality, profile, weight }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use chromagram::{Chromagram, ChromagramInitProps};
+    use super::*;
+
+    fn chroma_of(pitch_classes: &[usize]) -> Vec<f64> {
+        let mut chroma = vec![0.05; 12];
+        for &pc in pitch_classes {
+            chroma[pc] = 1.0;
+        }
+        chroma
+    }
+
+    #[test]
+    fn detects_chord_types() {
+        let mut detector = TemplateChordDetector::new(TemplateChordDetectorInitProps::default());
+
+        let c_major = detector.detect(&chroma_of(&[0, 4, 7])).unwrap();
+        assert_eq!((c_major.root, c_major.quality), (0, ChordQuality::Major));
+
+        let a_minor = detector.detect(&chroma_of(&[9, 0, 4])).unwrap();
+        assert_eq!((a_minor.root, a_minor.quality), (9, ChordQuality::Minor));
+
+        let g7 = detector.detect(&chroma_of(&[7, 11, 2, 5])).unwrap();
+        assert_eq!((g7.root, g7.quality), (7, ChordQuality::Dominant7));
+        assert_eq!(g7.to_string(), "G7");
+    }
+
+    #[test]
+    fn detects_chord_with_harmonic_templates() {
+        let mut detector = TemplateChordDetector::new(TemplateChordDetectorInitProps {
+            num_harmonics: 4,
+            ..Default::default()
+        });
+
+        let e_minor = detector.detect(&chroma_of(&[4, 7, 11])).unwrap();
+        assert_eq!(e_minor.to_string(), "Em");
+    }
+
+    #[test]
+    fn no_chord_in_silence() {
+        let mut detector = TemplateChordDetector::new(TemplateChordDetectorInitProps { no_chord_threshold: 0.5, ..Default::default() });
+        assert_eq!(detector.detect(&[0.0; 12]), None);
+        assert_eq!(detector.detect(&[f64::NAN; 12]), None);
+        assert_eq!(detector.detect_with_bass(&[0.01; 12], &[1.0; 12]), None);
+        let mut nan_bass = [0.0; 12];
+        nan_bass[4] = f64::NAN;
+        assert_eq!(detector.detect_with_bass(&chroma_of(&[0, 4, 7]), &nan_bass), None);
+        assert_eq!(detector.detect_with_bass(&chroma_of(&[0, 4, 7]), &[f64::INFINITY; 12]), None);
+        assert!(detector.detect(&chroma_of(&[0, 4, 7])).is_some());
+    }
+
+    #[test]
+    fn detects_slash_chords() {
+        let mut detector = TemplateChordDetector::new(TemplateChordDetectorInitProps::default());
+        let d_major = chroma_of(&[2, 6, 9]);
+
+        let mut bass_chroma = vec![0.0; 12];
+        assert_eq!(detector.detect_with_bass(&d_major, &bass_chroma).unwrap().to_string(), "D");
+
+        bass_chroma[6] = 1.0;
+        let inversion = detector.detect_with_bass(&d_major, &bass_chroma).unwrap();
+        assert_eq!((inversion.root, inversion.bass), (2, 6));
+        assert_eq!(inversion.to_string(), "D/F#");
+
+        bass_chroma[2] = 1.0;
+        assert_eq!(detector.detect_with_bass(&d_major, &bass_chroma).unwrap().to_string(), "D");
+    }
+
+    #[test]
+    fn detects_slash_chord_from_chromagram() {
+        let props = ChromagramInitProps::default();
+        let sample_rate = props.sample_rate as f64;
+        let frame_size = props.frame_size;
+        let mut chromagram = Chromagram::new(props).unwrap();
+
+        // C2 in the bass, A3, C4, E4
+        let frequencies = [65.41, 220.0, 261.63, 329.63];
+        let signal: Vec<f64> = (0..frame_size * 64)
+            .map(|i| frequencies.iter().map(|f| (2.0 * PI * f * i as f64 / sample_rate).sin()).sum())
+            .collect();
+        for frame in signal.chunks(frame_size) {
+            chromagram.process_audio_frame(frame).unwrap();
+        }
+
+        let mut detector = TemplateChordDetector::new(TemplateChordDetectorInitProps::default());
+        let chord = detector.detect_with_bass(&chromagram.chromagram, &chromagram.bass_chromagram).unwrap();
+        assert_eq!(chord.to_string(), "Am/C");
+    }
+
+    #[test]
+    fn detects_chord_from_chromagram() {
+        let props = ChromagramInitProps::default();
+        let sample_rate = props.sample_rate as f64;
+        let frame_size = props.frame_size;
+        let mut chromagram = Chromagram::new(props).unwrap();
+
+        // A3, C4, E4
+        let frequencies = [220.0, 261.63, 329.63];
+        let signal: Vec<f64> = (0..frame_size * 64)
+            .map(|i| frequencies.iter().map(|f| (2.0 * PI * f * i as f64 / sample_rate).sin()).sum())
+            .collect();
+        for frame in signal.chunks(frame_size) {
+            chromagram.process_audio_frame(frame).unwrap();
+        }
+
+        let mut detector = TemplateChordDetector::new(TemplateChordDetectorInitProps::default());
+        assert_eq!(detector.detect(&chromagram.chromagram).unwrap().to_string(), "Am");
+    }
+}
@@ -0,0 +1,143 @@
+use std::f64;
+use std::f64::consts::PI;
+
+use spectral_peaks::spectral_peaks;
+
+const A4_FREQUENCY: f64 = 440.0;
+const A4_MIDI_NOTE: f64 = 69.0;
+
+/// Deviation of the reference tuning from A4 = 440 Hz in cents.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Tuning {
+    pub cents: f64,
+}
+
+impl Tuning {
+    pub fn from_cents(cents: f64) -> Self {
+        Tuning { cents }
+    }
+
+    /// Creates the tuning of a given A4 frequency, e.g. 432 Hz.
+    pub fn from_reference_frequency(a4_frequency: f64) -> Self {
+        Tuning { cents: 1200.0 * (a4_frequency / A4_FREQUENCY).log2() }
+    }
+
+    /// Frequency of A4 in this tuning.
+    pub fn reference_frequency(&self) -> f64 {
+        A4_FREQUENCY * self.ratio()
+    }
+
+    /// Frequency factor between this tuning and A4 = 440 Hz.
+    pub fn ratio(&self) -> f64 {
+        f64::powf(2.0, self.cents / 1200.0)
+    }
+
+    pub fn note_frequency(&self, midi_note: f64) -> f64 {
+        f64::powf(2.0, (midi_note - A4_MIDI_NOTE) / 12.0) * self.reference_frequency()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TuningEstimatorInitProps {
+    /// Only spectral peaks between min_frequency and max_frequency are considered.
+    pub min_frequency: f64,
+    pub max_frequency: f64,
+    /// Peaks below this fraction of the strongest peak in a spectrum are ignored.
+    pub peak_threshold: f64,
+}
+
+impl Default for TuningEstimatorInitProps {
+    fn default() -> Self {
+        Self { min_frequency: 60.0, max_frequency: 2000.0, peak_threshold: 0.1 }
+    }
+}
+
+/// Estimates the tuning from the spectral peaks of one or more magnitude spectra.
+///
+/// The deviation of every peak from the nearest equal tempered semitone (A4 = 440 Hz)
+/// is accumulated as a magnitude weighted circular mean, because a deviation of +50 cents
+/// is the same as one of -50 cents from the next semitone.
+pub struct TuningEstimator {
+    props: TuningEstimatorInitProps,
+    sum_cos: f64,
+    sum_sin: f64,
+}
+
+impl TuningEstimator {
+    pub fn new(props: TuningEstimatorInitProps) -> Self {
+        Self { props, sum_cos: 0.0, sum_sin: 0.0 }
+    }
+
+    pub fn reset(&mut self) {
+        self.sum_cos = 0.0;
+        self.sum_sin = 0.0;
+    }
+
+    /// Adds the peaks of a magnitude spectrum (bins 0 to N/2) to the estimate.
+    /// `bin_frequency` is the distance of two bins in Hz, i.e. sample_rate / fft_size.
+    pub fn process_magnitude_spectrum(&mut self, magnitude_spectrum: &[f64], bin_frequency: f64) {
+        let peaks = spectral_peaks(magnitude_spectrum, bin_frequency,
+                                   self.props.min_frequency, self.props.max_frequency, self.props.peak_threshold);
+        for peak in peaks {
+            let midi_note = A4_MIDI_NOTE + 12.0 * (peak.frequency / A4_FREQUENCY).log2();
+            let angle = 2.0 * PI * (midi_note - midi_note.round());
+            self.sum_cos += peak.magnitude * angle.cos();
+            self.sum_sin += peak.magnitude * angle.sin();
+        }
+    }
+
+    pub fn tuning(&self) -> Tuning {
+        if self.sum_cos == 0.0 && self.sum_sin == 0.0 {
+            return Tuning::default();
+        }
+        Tuning::from_cents(self.sum_sin.atan2(self.sum_cos) / (2.0 * PI) * 100.0)
+    }
+}
+
+/// Estimates the tuning of a single magnitude spectrum.
+pub fn estimate_tuning(magnitude_spectrum: &[f64], bin_frequency: f64) -> Tuning {
+    let mut estimator = TuningEstimator::new(TuningEstimatorInitProps::default());
+    estimator.process_magnitude_spectrum(magnitude_spectrum, bin_frequency);
+    estimator.tuning()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustfft::FFTplanner;
+    use rustfft::num_complex::Complex;
+    use rustfft::num_traits::Zero;
+
+    fn magnitude_spectrum(signal: &[f64]) -> Vec<f64> {
+        let n = signal.len();
+        let mut input: Vec<Complex<f64>> = signal.iter().enumerate()
+            .map(|(i, &x)| Complex::new(x * (0.5 - 0.5 * (2.0 * PI * i as f64 / n as f64).cos()), 0.0))
+            .collect();
+        let mut output = vec![Complex::zero(); n];
+        FFTplanner::new(false).plan_fft(n).process(&mut input, &mut output);
+        output[..n / 2 + 1].iter().map(|c| c.norm()).collect()
+    }
+
+    #[test]
+    fn reference_frequency() {
+        let tuning = Tuning::from_reference_frequency(432.0);
+        assert!((tuning.cents + 31.77).abs() < 0.01);
+        assert!((tuning.reference_frequency() - 432.0).abs() < 1e-9);
+        assert!((tuning.note_frequency(57.0) - 216.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn estimates_tuning_of_a432_chord() {
+        let sample_rate = 44_100.0;
+        let tuning = Tuning::from_reference_frequency(432.0);
+        let signal: Vec<f64> = (0..8192)
+            .map(|i| [57.0, 61.0, 64.0].iter()
+                .map(|&note| (2.0 * PI * tuning.note_frequency(note) * i as f64 / sample_rate).sin())
+                .sum())
+            .collect();
+
+        let estimate = estimate_tuning(&magnitude_spectrum(&signal), sample_rate / 8192.0);
+        assert!((estimate.cents - tuning.cents).abs() < 3.0, "estimate = {:?}", estimate);
+    }
+}
@@ -1,7 +1,9 @@
-pub fn make_mono(channel_count: usize, input: &[i16], output: &mut [i16]) {
-    let channel_count_i64 = channel_count as i64;
+use sample::Sample;
+
+/// Averages the channels of interleaved samples.
+pub fn make_mono<S: Sample>(channel_count: usize, input: &[S], output: &mut [S]) {
     for (chunk, out) in input.chunks(channel_count).zip(output.iter_mut()) {
-        let s: i64 = chunk.iter().map(|&x| x as i64).sum();
-        *out = (s / channel_count_i64) as i16;
+        let sum: f64 = chunk.iter().map(|&x| x.to_f64()).sum();
+        *out = S::from_f64(sum / channel_count as f64);
     }
 }
\ No newline at end of file
@@ -0,0 +1,80 @@
+use std::f64;
+use std::f64::consts::PI;
+
+/// Window functions to trade spectral leakage against frequency resolution.
+///
+/// All windows are periodic (the value at index `len` would equal the one at 0),
+/// which is the right choice for spectral analysis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WindowFunction {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+    BlackmanHarris,
+    /// Larger beta means less leakage and a wider main lobe, e.g. 8.6 is similar to Blackman
+    Kaiser { beta: f64 },
+}
+
+impl WindowFunction {
+    pub fn value(&self, i: usize, len: usize) -> f64 {
+        let x = i as f64 / len as f64;
+        let cosine_sum = |coefficients: &[f64]| -> f64 {
+            coefficients.iter().enumerate()
+                .map(|(k, &a)| if k % 2 == 0 { a } else { -a } * f64::cos(2.0 * PI * k as f64 * x))
+                .sum()
+        };
+
+        match *self {
+            WindowFunction::Rectangular => 1.0,
+            WindowFunction::Hann => cosine_sum(&[0.5, 0.5]),
+            WindowFunction::Hamming => cosine_sum(&[0.54, 0.46]),
+            WindowFunction::Blackman => cosine_sum(&[0.42, 0.5, 0.08]),
+            WindowFunction::BlackmanHarris => cosine_sum(&[0.358_75, 0.488_29, 0.141_28, 0.011_68]),
+            WindowFunction::Kaiser { beta } => {
+                let r = 2.0 * x - 1.0;
+                bessel_i0(beta * (1.0 - r * r).max(0.0).sqrt()) / bessel_i0(beta)
+            }
+        }
+    }
+
+    pub fn generate(&self, len: usize) -> Vec<f64> {
+        (0..len).map(|i| self.value(i, len)).collect()
+    }
+}
+
+/// Modified Bessel function of the first kind, order 0, by its power series.
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half_x_squared = x * x / 4.0;
+    for k in 1..100 {
+        term *= half_x_squared / (k * k) as f64;
+        sum += term;
+        if term < sum * 1e-16 {
+            break;
+        }
+    }
+    sum
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_shapes() {
+        let windows = [WindowFunction::Hann, WindowFunction::Hamming, WindowFunction::Blackman,
+            WindowFunction::BlackmanHarris, WindowFunction::Kaiser { beta: 8.6 }];
+        for window in windows.iter() {
+            let values = window.generate(64);
+            assert!((values[32] - 1.0).abs() < 1e-9, "{:?} peak = {}", window, values[32]);
+            assert!(values[0] < 0.1, "{:?} edge = {}", window, values[0]);
+            // symmetric around the center
+            assert!((values[10] - values[54]).abs() < 1e-9);
+        }
+        assert_eq!(WindowFunction::Rectangular.generate(3), vec![1.0; 3]);
+        assert!((WindowFunction::Hamming.value(0, 64) - 0.08).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,96 @@
+extern crate chord_detection;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::f64::consts::PI;
+
+use chord_detection::chromagram::{ChromaMethod, Chromagram, ChromagramInitProps};
+use chord_detection::gromagram::{Gromagram, GromagramInitProps};
+use chord_detection::hpcp::HpcpInitProps;
+use chord_detection::nnls_chroma::NnlsChromaInitProps;
+use chord_detection::spectrum_analyzer::SpectrumAnalyzer;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn count_allocations<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.with(|count| count.get());
+    f();
+    ALLOCATIONS.with(|count| count.get()) - before
+}
+
+fn sine(frequency: f64, length: usize) -> Vec<f64> {
+    (0..length).map(|i| (2.0 * PI * frequency * i as f64 / 44_100.0).sin()).collect()
+}
+
+#[test]
+fn spectrum_analyzer_does_not_allocate() {
+    let samples = sine(440.0, 4096);
+    let mut analyzer = SpectrumAnalyzer::new(4096);
+
+    let allocations = count_allocations(|| {
+        for _ in 0..10 {
+            analyzer.magnitude_spectrum(samples.iter().cloned());
+            analyzer.power_spectrum(samples.iter().cloned());
+        }
+    });
+
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn chromagram_does_not_allocate() {
+    let chroma_methods = [
+        ChromaMethod::MaxBin,
+        ChromaMethod::Hpcp(HpcpInitProps::default()),
+        ChromaMethod::Nnls(NnlsChromaInitProps::default()),
+    ];
+    for chroma_method in &chroma_methods {
+        let props = ChromagramInitProps::default().with_auto_tuning(true).with_chroma_method(chroma_method.clone());
+        let frame_size = props.frame_size;
+        let samples = sine(440.0, frame_size * 100);
+        let mut chromagram = Chromagram::new(props).unwrap();
+
+        let allocations = count_allocations(|| {
+            for frame in samples.chunks(frame_size) {
+                chromagram.process_audio_frame(frame).unwrap();
+            }
+        });
+
+        assert_eq!(allocations, 0);
+    }
+}
+
+#[test]
+fn gromagram_does_not_allocate() {
+    for &sliding in &[false, true] {
+        let props = GromagramInitProps { notes_count: 48, auto_tuning: true, sliding, ..Default::default() };
+        let samples: Vec<i16> = sine(440.0, 256 * 100).iter().map(|x| (x * 10_000.0) as i16).collect();
+        let mut gromagram = Gromagram::new(props);
+
+        let allocations = count_allocations(|| {
+            for frame in samples.chunks(256) {
+                gromagram.process_audio_frame(frame);
+            }
+        });
+
+        assert_eq!(allocations, 0);
+    }
+}